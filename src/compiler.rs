@@ -2,11 +2,15 @@ pub mod intermediate_code_generator;
 pub mod lexer;
 pub mod native_code_generator;
 pub mod parser;
+pub mod type_checker;
+
+use std::fmt;
 
 pub use intermediate_code_generator::generate_intermediate_code;
 pub use lexer::lexing;
 pub use native_code_generator::generate_native_code;
 pub use parser::parsing;
+pub use type_checker::type_checking;
 
 pub enum LetterKind {
     LtNum,
@@ -16,6 +20,7 @@ pub enum LetterKind {
     LtParenthesis,
     LtSymbol,
     LtAlphabet,
+    LtQuote,
 }
 
 pub enum LexerCondition {
@@ -23,6 +28,8 @@ pub enum LexerCondition {
     CondMiddleOfNumber,
     CondMiddleOfComparisonOperator,
     CondMiddleOfVariable,
+    CondMiddleOfString,
+    CondMiddleOfCharacter,
 }
 
 pub enum TokenKind {
@@ -33,6 +40,35 @@ pub enum TokenKind {
     TkSymbol,
     TkVariable,
     TkReturn,
+    TkIf,
+    TkElse,
+    TkWhile,
+    TkFor,
+    TkBreak,
+    TkContinue,
+    TkInt,
+    TkExtern,
+    TkChar,
+    TkLong,
+    TkString,
+    TkStruct,
+    TkUnion,
+    TkSizeof,
+    TkSwitch,
+    TkCase,
+    TkDefault,
+    TkDo,
+    TkGoto,
+    TkTypedef,
+    TkEnum,
+    TkSigned,
+    TkUnsigned,
+    TkFloat,
+    TkDouble,
+    TkStatic,
+    TkConst,
+    TkVolatile,
+    TkVoid,
 }
 
 pub struct Token {
@@ -40,6 +76,7 @@ pub struct Token {
     pub val: String,
 }
 
+#[derive(Clone)]
 pub enum NodeKind {
     NdNum,
     NdOperator,
@@ -47,11 +84,301 @@ pub enum NodeKind {
     NdAssignOperator,
     NdVariable,
     NdReturn,
+    NdExpressionStatement,
+    NdIf,
+    NdWhile,
+    NdFor,
+    NdBreak,
+    NdContinue,
+    NdBlock,
+    NdDeclaration,
+    NdFunction,
+    NdCall,
+    NdFunctionDeclaration,
+    NdCast,
+    NdAddress,
+    NdDereference,
+    NdString,
+    NdMember,
+    NdSizeof,
+    NdLogicalOperator,
+    NdBitwiseNot,
+    NdUnaryOperator,
+    NdPostfixOperator,
+    NdComma,
+    NdConditional,
+    NdSwitch,
+    NdCase,
+    NdDoWhile,
+    NdGoto,
+    NdLabel,
+    NdTypedef,
+    NdVaStart,
+    NdInitializer,
+    NdDesignator,
+}
+
+// A static variable or function is local to its object file, and an extern declaration
+// refers to a definition that may be in another one.
+#[derive(Clone, PartialEq)]
+pub enum StorageClass {
+    ScNone,
+    ScStatic,
+    ScExtern,
 }
 
+#[derive(Clone)]
 pub struct Node {
     pub val: String,
     pub kind: NodeKind,
     pub left: Option<Box<Node>>,
     pub right: Option<Box<Node>>,
+    pub cond: Option<Box<Node>>,
+    pub then: Option<Box<Node>>,
+    pub els: Option<Box<Node>>,
+    pub init: Option<Box<Node>>,
+    pub inc: Option<Box<Node>>,
+    pub body: Vec<Node>,
+    pub params: Vec<Node>,
+    pub args: Vec<Node>,
+    pub ty: Option<Type>,
+    pub offset: usize,
+    // the number of the arguments before `...` of a variadic function or of a call to one
+    pub fixed_arg_count: Option<usize>,
+    // the storage class of a declaration or a function
+    pub storage_class: StorageClass,
+}
+
+impl Node {
+    pub fn new(val: &str, kind: NodeKind) -> Node {
+        Node {
+            val: String::from(val),
+            kind,
+            left: None,
+            right: None,
+            cond: None,
+            then: None,
+            els: None,
+            init: None,
+            inc: None,
+            body: Vec::new(),
+            params: Vec::new(),
+            args: Vec::new(),
+            ty: None,
+            offset: 0,
+            fixed_arg_count: None,
+            storage_class: StorageClass::ScNone,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub enum TypeKind {
+    TyVoid,
+    TyChar,
+    TyInt,
+    TyLong,
+    TyFloat,
+    TyDouble,
+    TyPointer,
+    TyArray,
+    TyStruct,
+    TyUnion,
+    TyEnum,
+    TyTypedef,
+    TyFunction,
+}
+
+// A struct, union or enum is identified by the index of the token that begins its definition.
+// a type that only names a tag (e.g. `struct node *next`) has no members and no `struct_id`
+// until the type checker resolves it, and neither has a typedef name, which is kept in `tag`.
+// An enum is an int once the type checker has defined its enumerators.
+// A function type keeps its return type in `base` and the types of its parameters in `param_tys`,
+// which are followed by any number of arguments if it `is_variadic`.
+#[derive(Clone)]
+pub struct Type {
+    pub kind: TypeKind,
+    pub size: usize,
+    pub align: usize,
+    // an unsigned integer is kept zero-extended to 64 bits, and a signed one sign-extended
+    pub is_unsigned: bool,
+    // an object of a const type cannot be assigned to
+    pub is_const: bool,
+    pub base: Option<Box<Type>>,
+    pub array_len: usize,
+    // a length that is not a number literal (e.g. an enumerator) is computed by the type checker
+    pub array_len_expr: Option<Box<Node>>,
+    pub tag: String,
+    pub members: Vec<Member>,
+    pub enumerators: Vec<Enumerator>,
+    pub struct_id: Option<usize>,
+    pub param_tys: Vec<Type>,
+    pub is_variadic: bool,
+}
+
+#[derive(Clone)]
+pub struct Member {
+    pub name: String,
+    pub ty: Type,
+    pub offset: usize,
+}
+
+// An enumerator without a value is one more than the previous one (or 0 if it is the first).
+#[derive(Clone)]
+pub struct Enumerator {
+    pub name: String,
+    pub value: Option<Node>,
+}
+
+impl Type {
+    pub fn new(kind: TypeKind) -> Type {
+        // void has the size 1 so that a pointer to void advances by bytes
+        let size = match kind {
+            TypeKind::TyVoid | TypeKind::TyChar => 1,
+            TypeKind::TyInt | TypeKind::TyEnum | TypeKind::TyFloat => 4,
+            TypeKind::TyLong | TypeKind::TyDouble | TypeKind::TyPointer => 8,
+            TypeKind::TyArray
+            | TypeKind::TyStruct
+            | TypeKind::TyUnion
+            | TypeKind::TyTypedef
+            | TypeKind::TyFunction => 0,
+        };
+        Type {
+            kind,
+            size,
+            align: size.max(1),
+            is_unsigned: false,
+            is_const: false,
+            base: None,
+            array_len: 0,
+            array_len_expr: None,
+            tag: String::new(),
+            members: Vec::new(),
+            enumerators: Vec::new(),
+            struct_id: None,
+            param_tys: Vec::new(),
+            is_variadic: false,
+        }
+    }
+
+    pub fn pointer_to(base: Type) -> Type {
+        let mut ty = Type::new(TypeKind::TyPointer);
+        ty.base = Some(Box::new(base));
+        ty
+    }
+
+    pub fn array_of(base: Type, len: usize) -> Type {
+        let mut ty = Type::new(TypeKind::TyArray);
+        ty.size = base.size * len;
+        ty.align = base.align;
+        ty.base = Some(Box::new(base));
+        ty.array_len = len;
+        ty
+    }
+
+    pub fn function_returning(return_ty: Type, param_tys: Vec<Type>) -> Type {
+        let mut ty = Type::new(TypeKind::TyFunction);
+        ty.base = Some(Box::new(return_ty));
+        ty.param_tys = param_tys;
+        ty
+    }
+
+    pub fn is_void(&self) -> bool {
+        matches!(self.kind, TypeKind::TyVoid)
+    }
+
+    pub fn is_integer(&self) -> bool {
+        matches!(
+            self.kind,
+            TypeKind::TyChar | TypeKind::TyInt | TypeKind::TyLong | TypeKind::TyEnum
+        )
+    }
+
+    pub fn is_floating(&self) -> bool {
+        matches!(self.kind, TypeKind::TyFloat | TypeKind::TyDouble)
+    }
+
+    // integers and floating-point numbers
+    pub fn is_arithmetic(&self) -> bool {
+        self.is_integer() || self.is_floating()
+    }
+
+    pub fn is_pointer(&self) -> bool {
+        matches!(self.kind, TypeKind::TyPointer)
+    }
+
+    pub fn is_array(&self) -> bool {
+        matches!(self.kind, TypeKind::TyArray)
+    }
+
+    pub fn is_struct(&self) -> bool {
+        matches!(self.kind, TypeKind::TyStruct | TypeKind::TyUnion)
+    }
+
+    pub fn is_function(&self) -> bool {
+        matches!(self.kind, TypeKind::TyFunction)
+    }
+
+    // An array used as a value is converted to a pointer to its first element,
+    // and a function to a pointer to the function.
+    pub fn decay(&self) -> Type {
+        match &self.base {
+            Some(base) if self.is_array() => Type::pointer_to((**base).clone()),
+            _ if self.is_function() => Type::pointer_to(self.clone()),
+            _ => self.clone(),
+        }
+    }
+}
+
+// Two types are the same if they are built the same way from the same struct definitions;
+// members and sizes are left out because a tag-only type has neither, and such a type
+// (e.g. a pointer declared before its struct is defined) matches any definition of its tag.
+// const is left out as well, so that a value can be passed to a pointer to const.
+impl PartialEq for Type {
+    fn eq(&self, other: &Type) -> bool {
+        self.kind == other.kind
+            && self.is_unsigned == other.is_unsigned
+            && self.base == other.base
+            && self.array_len == other.array_len
+            && self.tag == other.tag
+            && self.param_tys == other.param_tys
+            && self.is_variadic == other.is_variadic
+            && (self.struct_id == other.struct_id
+                || self.struct_id.is_none()
+                || other.struct_id.is_none())
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_const && !self.is_pointer() {
+            write!(f, "const ")?;
+        }
+        if self.is_unsigned {
+            write!(f, "unsigned ")?;
+        }
+        match (&self.kind, &self.base) {
+            (TypeKind::TyPointer, Some(base)) if self.is_const => write!(f, "{} *const", base),
+            (TypeKind::TyPointer, Some(base)) => write!(f, "{} *", base),
+            (TypeKind::TyArray, Some(base)) => write!(f, "{}[{}]", base, self.array_len),
+            (TypeKind::TyFunction, Some(base)) => {
+                let mut param_tys: Vec<String> =
+                    self.param_tys.iter().map(|ty| ty.to_string()).collect();
+                if self.is_variadic {
+                    param_tys.push(String::from("..."));
+                }
+                write!(f, "{} ({})", base, param_tys.join(", "))
+            }
+            (TypeKind::TyVoid, _) => write!(f, "void"),
+            (TypeKind::TyChar, _) => write!(f, "char"),
+            (TypeKind::TyInt, _) => write!(f, "int"),
+            (TypeKind::TyFloat, _) => write!(f, "float"),
+            (TypeKind::TyDouble, _) => write!(f, "double"),
+            (TypeKind::TyStruct, _) => write!(f, "struct {}", self.tag),
+            (TypeKind::TyUnion, _) => write!(f, "union {}", self.tag),
+            (TypeKind::TyEnum, _) => write!(f, "enum {}", self.tag),
+            (TypeKind::TyTypedef, _) => write!(f, "{}", self.tag),
+            _ => write!(f, "long"),
+        }
+    }
 }
@@ -1,79 +1,999 @@
 use crate::compiler::*;
+use std::cmp::Ordering;
 
-pub fn generate_intermediate_code(ast: &Vec<Option<Node>>) -> Vec<String> {
+// Bookkeeping for generated labels.
+// `break_labels` and `continue_labels` are stacks whose tops belong to the innermost enclosing loop.
+// `strings` holds the contents of the string literals, each of which is labeled by its index,
+// and `floats` the sizes and bit patterns of the floating-point constants, labeled the same way.
+struct LabelContext {
+    count: usize,
+    break_labels: Vec<String>,
+    continue_labels: Vec<String>,
+    strings: Vec<Vec<u8>>,
+    floats: Vec<(usize, u64)>,
+    // the number of enclosing switch statements
+    switches: usize,
+    // labels of goto statements are resolved within the current function
+    function_name: String,
+    defined_labels: Vec<String>,
+    goto_labels: Vec<String>,
+    // the definitions of static local variables, which are placed with the global ones
+    statics: Vec<String>,
+}
+
+pub fn generate_intermediate_code(ast: &[Option<Node>]) -> Vec<String> {
     let mut mid_commands: Vec<String> = Vec::new();
-    for node in ast.iter() {
-        match node {
-            Some(node) => {
-                traverse(&node, &mut mid_commands);
+    let mut labels = LabelContext {
+        count: 0,
+        break_labels: Vec::new(),
+        continue_labels: Vec::new(),
+        strings: Vec::new(),
+        floats: Vec::new(),
+        switches: 0,
+        function_name: String::new(),
+        defined_labels: Vec::new(),
+        goto_labels: Vec::new(),
+        statics: Vec::new(),
+    };
+    for node in ast.iter().flatten() {
+        if let NodeKind::NdDeclaration = node.kind {
+            for declarator in node
+                .body
+                .iter()
+                .filter(|declarator| !is_function(declarator))
+            {
+                declare_global_variable(node, declarator, &mut mid_commands, &mut labels);
             }
-            _ => {}
+        } else {
+            traverse(node, &mut mid_commands, &mut labels);
         }
     }
-    mid_commands
+
+    // the string literals, floating-point constants and static local variables are defined first
+    // so that they are known wherever they are used
+    let mut string_commands: Vec<String> = Vec::new();
+    for (i, bytes) in labels.strings.iter().enumerate() {
+        let bytes: Vec<String> = bytes.iter().map(|byte| byte.to_string()).collect();
+        string_commands.push(format!("STRING .L.str.{} {}", i, bytes.join(" ")));
+    }
+    for (i, (size, bits)) in labels.floats.iter().enumerate() {
+        string_commands.push(format!("FLOAT .L.float.{} {} {}", i, size, bits));
+    }
+    string_commands.append(&mut labels.statics);
+    string_commands.append(&mut mid_commands);
+    string_commands
 }
 
-fn traverse(node: &Node, mid_commands: &mut Vec<String>) {
-    if let NodeKind::NdAssignOperator = node.kind {
-        let variable_name;
-        match &node.left {
-            Some(node_left) => match node_left.kind {
-                NodeKind::NdVariable => {
-                    variable_name = &node_left.val[..];
+// An extern variable is only declared by `EXTERN name`, while a static one is defined
+// after `LOCAL name`, which keeps its name from being seen by other object files.
+fn declare_global_variable(
+    node: &Node,
+    declarator: &Node,
+    mid_commands: &mut Vec<String>,
+    labels: &mut LabelContext,
+) {
+    match node.storage_class {
+        StorageClass::ScExtern => {
+            mid_commands.push(format!("EXTERN {}", declarator.val));
+        }
+        StorageClass::ScStatic => {
+            mid_commands.push(format!("LOCAL {}", declarator.val));
+            define_global_variable(declarator, mid_commands, labels);
+        }
+        StorageClass::ScNone => {
+            define_global_variable(declarator, mid_commands, labels);
+        }
+    }
+}
+
+// A global variable without an initializer is `GLOBAL name size align`, while an initialized
+// one is `DATA name align` followed by its bytes: `VALUE size value` for each value given
+// by the initializer and `ZERO size` for the gaps between them.
+// An address constant is given by its symbol, which the linker resolves, and its offset.
+fn define_global_variable(
+    declarator: &Node,
+    mid_commands: &mut Vec<String>,
+    labels: &mut LabelContext,
+) {
+    let node_init = match &declarator.init {
+        Some(node_init) => node_init,
+        _ => {
+            mid_commands.push(format!(
+                "GLOBAL {} {} {}",
+                declarator.val,
+                size_of(declarator),
+                align_of(declarator)
+            ));
+            return;
+        }
+    };
+    // a const variable is read-only unless the linker has to fill in an address in it
+    let mut values = Vec::new();
+    let mut has_address = false;
+    let mut offset = 0;
+    for designator in node_init.body.iter() {
+        if offset < designator.offset {
+            values.push(format!("ZERO {}", designator.offset - offset));
+        }
+        // a floating-point value is given by its bit pattern
+        let value = match &designator.left {
+            Some(value) if is_floating(value) => float_bits(value, eval_float(value)).1.to_string(),
+            Some(value) => match eval_address(value, labels) {
+                (Some(symbol), offset) if size_of(designator) == 8 => {
+                    has_address = true;
+                    format_address(&symbol, offset)
+                }
+                (Some(_), _) => {
+                    panic!("Initializer element is not constant");
+                }
+                (_, value) => value.to_string(),
+            },
+            _ => {
+                panic!("Expected initializer of {}", declarator.val);
+            }
+        };
+        values.push(format!("VALUE {} {}", size_of(designator), value));
+        offset = designator.offset + size_of(designator);
+    }
+    if offset < size_of(declarator) {
+        values.push(format!("ZERO {}", size_of(declarator) - offset));
+    }
+    let section = match &declarator.ty {
+        Some(ty) if is_read_only(ty) && !has_address => "RODATA",
+        _ => "DATA",
+    };
+    mid_commands.push(format!(
+        "{} {} {}",
+        section,
+        declarator.val,
+        align_of(declarator)
+    ));
+    mid_commands.append(&mut values);
+}
+
+// an array of const elements is const as a whole
+fn is_read_only(ty: &Type) -> bool {
+    match &ty.base {
+        Some(base) if ty.is_array() => is_read_only(base),
+        _ => ty.is_const,
+    }
+}
+
+// A local variable with an initializer list is zero-filled except where a value is given,
+// and each value is stored at its offset from the address of the variable.
+fn initialize_local_variable(
+    declarator: &Node,
+    node_init: &Node,
+    mid_commands: &mut Vec<String>,
+    labels: &mut LabelContext,
+) {
+    let mut offset = 0;
+    for designator in node_init.body.iter() {
+        zero_fill(declarator, offset, designator.offset, mid_commands);
+        mid_commands.push(format!("ADDRESS {}", declarator.val));
+        mid_commands.push(format!("MEMBER {}", designator.offset));
+        if let Some(value) = &designator.left {
+            traverse(value, mid_commands, labels);
+        }
+        if is_aggregate(designator) {
+            mid_commands.push(format!("COPY {}", size_of(designator)));
+        } else {
+            mid_commands.push(format!("ASSIGN {}", size_of(designator)));
+        }
+        mid_commands.push(String::from("POP"));
+        offset = designator.offset + size_of(designator);
+    }
+    zero_fill(declarator, offset, size_of(declarator), mid_commands);
+}
+
+// Stores zeros from the offset begin to end of a local variable, in the largest pieces
+// that are aligned as the variable is.
+fn zero_fill(declarator: &Node, begin: usize, end: usize, mid_commands: &mut Vec<String>) {
+    let mut offset = begin;
+    while offset < end {
+        let size = [8, 4, 2, 1]
+            .iter()
+            .copied()
+            .find(|size| {
+                *size <= align_of(declarator)
+                    && offset.is_multiple_of(*size)
+                    && offset + size <= end
+            })
+            .unwrap_or(1);
+        mid_commands.push(format!("ADDRESS {}", declarator.val));
+        mid_commands.push(format!("MEMBER {}", offset));
+        mid_commands.push(String::from("PUSH 0"));
+        mid_commands.push(format!("ASSIGN {}", size));
+        mid_commands.push(String::from("POP"));
+        offset += size;
+    }
+}
+
+// Evaluates an expression that must be known at compile time, such as an initializer
+// of a global variable or the value of an enumerator.
+pub fn eval(node: &Node) -> i64 {
+    let operands = || match (&node.left, &node.right) {
+        (Some(node_left), Some(node_right)) => (eval(node_left), eval(node_right)),
+        _ => {
+            panic!("Expected operands of {}", node.val);
+        }
+    };
+    match node.kind {
+        NodeKind::NdNum => node.val.parse().unwrap(),
+        NodeKind::NdOperator => {
+            let (left, right) = operands();
+            // unsigned operands are divided and shifted as their type
+            let (left, right) = if is_unsigned(node) {
+                (truncate(node, left), truncate(node, right))
+            } else {
+                (left, right)
+            };
+            let value = match (&node.val[..], is_unsigned(node)) {
+                ("+", _) => left.wrapping_add(right),
+                ("-", _) => left.wrapping_sub(right),
+                ("*", _) => left.wrapping_mul(right),
+                ("/", false) => left.wrapping_div(right),
+                ("/", true) => (left as u64).wrapping_div(right as u64) as i64,
+                ("%", false) => left.wrapping_rem(right),
+                ("%", true) => (left as u64).wrapping_rem(right as u64) as i64,
+                ("&", _) => left & right,
+                ("|", _) => left | right,
+                ("^", _) => left ^ right,
+                ("<<", _) => left.wrapping_shl(right as u32),
+                (">>", false) => left.wrapping_shr(right as u32),
+                (">>", true) => (left as u64).wrapping_shr(right as u32) as i64,
+                _ => {
+                    panic!("Unexpected operator: {}", node.val);
+                }
+            };
+            truncate(node, value)
+        }
+        NodeKind::NdBitwiseNot => match &node.left {
+            Some(node_left) => truncate(node, !eval(node_left)),
+            _ => {
+                panic!("Expected operand of ~");
+            }
+        },
+        NodeKind::NdUnaryOperator => match &node.left {
+            Some(node_left) if node.val == "-" => truncate(node, eval(node_left).wrapping_neg()),
+            Some(node_left) => truncate(node, eval(node_left)),
+            _ => {
+                panic!("Expected operand of {}", node.val);
+            }
+        },
+        NodeKind::NdComparisonOperator => match (&node.left, &node.right) {
+            (Some(node_left), Some(node_right)) => {
+                // the operands have been converted to their common type
+                let ordering = if is_floating(node_left) {
+                    eval_float(node_left).partial_cmp(&eval_float(node_right))
+                } else if is_unsigned(node_left) {
+                    Some((eval(node_left) as u64).cmp(&(eval(node_right) as u64)))
+                } else {
+                    Some(eval(node_left).cmp(&eval(node_right)))
+                };
+                let value = match &node.val[..] {
+                    "==" => ordering == Some(Ordering::Equal),
+                    "!=" => ordering != Some(Ordering::Equal),
+                    "<" => ordering == Some(Ordering::Less),
+                    "<=" => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+                    _ => {
+                        panic!("Unexpected operator: {}", node.val);
+                    }
+                };
+                value as i64
+            }
+            _ => {
+                panic!("Expected operands of {}", node.val);
+            }
+        },
+        NodeKind::NdLogicalOperator => match (&node.left, &node.right) {
+            // the right operand is evaluated only if the left one does not decide the result
+            (Some(node_left), Some(node_right)) => match (&node.val[..], eval(node_left) != 0) {
+                ("&&", false) => 0,
+                ("||", true) => 1,
+                _ => (eval(node_right) != 0) as i64,
+            },
+            _ => {
+                panic!("Expected operands of {}", node.val);
+            }
+        },
+        NodeKind::NdConditional => match (&node.cond, &node.then, &node.els) {
+            (Some(node_cond), Some(node_then), Some(node_els)) => {
+                if eval(node_cond) != 0 {
+                    eval(node_then)
+                } else {
+                    eval(node_els)
+                }
+            }
+            _ => {
+                panic!("Expected operands of ?:");
+            }
+        },
+        NodeKind::NdCast => {
+            let value = match &node.left {
+                // a floating-point number is truncated toward zero
+                Some(node_left) if is_floating(node_left) && is_unsigned(node) => {
+                    eval_float(node_left) as u64 as i64
                 }
+                Some(node_left) if is_floating(node_left) => eval_float(node_left) as i64,
+                Some(node_left) => eval(node_left),
                 _ => {
-                    panic!("Expected variable to the left of =");
+                    panic!("Expected operand of cast");
                 }
+            };
+            truncate(node, value)
+        }
+        _ => {
+            panic!("Initializer element is not constant");
+        }
+    }
+}
+
+// Evaluates an initializer that may be an address constant, such as `&g`, `a + 1` or `"abc"`,
+// as its symbol and offset. An arithmetic constant has no symbol.
+fn eval_address(node: &Node, labels: &mut LabelContext) -> (Option<String>, i64) {
+    match node.kind {
+        // the value of an array or a function is its address
+        NodeKind::NdVariable | NodeKind::NdDereference | NodeKind::NdMember
+            if is_aggregate(node) =>
+        {
+            eval_lvalue_address(node, labels)
+        }
+        NodeKind::NdString => (Some(string_label(node, labels)), 0),
+        NodeKind::NdAddress => match &node.left {
+            Some(node_left) => eval_lvalue_address(node_left, labels),
+            _ => {
+                panic!("Expected operand of &");
+            }
+        },
+        NodeKind::NdCast => match &node.left {
+            Some(node_left) if !is_floating(node_left) => match eval_address(node_left, labels) {
+                (Some(symbol), offset) => (Some(symbol), offset),
+                _ => (None, eval(node)),
             },
+            _ => (None, eval(node)),
+        },
+        NodeKind::NdOperator if node.val == "+" || node.val == "-" => {
+            let (left, right) = match (&node.left, &node.right) {
+                (Some(node_left), Some(node_right)) => (
+                    eval_address(node_left, labels),
+                    eval_address(node_right, labels),
+                ),
+                _ => {
+                    panic!("Expected operands of {}", node.val);
+                }
+            };
+            match (left, right, &node.val[..]) {
+                ((Some(symbol), offset), (None, value), "+") => (Some(symbol), offset + value),
+                ((None, value), (Some(symbol), offset), "+") => (Some(symbol), value + offset),
+                ((Some(symbol), offset), (None, value), _) => (Some(symbol), offset - value),
+                ((None, _), (None, _), _) => (None, eval(node)),
+                _ => {
+                    panic!("Initializer element is not constant");
+                }
+            }
+        }
+        _ => (None, eval(node)),
+    }
+}
+
+// Evaluates the address of an lvalue in an address constant, such as `&s.member`.
+fn eval_lvalue_address(node: &Node, labels: &mut LabelContext) -> (Option<String>, i64) {
+    match node.kind {
+        NodeKind::NdVariable => (Some(node.val.clone()), 0),
+        NodeKind::NdDereference => match &node.left {
+            // the address of `*p` is the value of `p`
+            Some(node_left) => eval_address(node_left, labels),
+            _ => {
+                panic!("Expected operand of *");
+            }
+        },
+        NodeKind::NdMember => match &node.left {
+            Some(node_left) => {
+                let (symbol, offset) = eval_lvalue_address(node_left, labels);
+                (symbol, offset + node.offset as i64)
+            }
             _ => {
-                panic!("Expected variable to the left of =");
+                panic!("Expected operand of .");
+            }
+        },
+        _ => {
+            panic!("Initializer element is not constant");
+        }
+    }
+}
+
+// Formats a symbol and an offset as an operand of a data directive, e.g. `g+8`.
+fn format_address(symbol: &str, offset: i64) -> String {
+    match offset {
+        0 => symbol.to_string(),
+        _ if offset < 0 => format!("{}{}", symbol, offset),
+        _ => format!("{}+{}", symbol, offset),
+    }
+}
+
+// Returns the label of a string literal; identical string literals share the same label.
+fn string_label(node: &Node, labels: &mut LabelContext) -> String {
+    let mut bytes = lexer::decode_escapes(&node.val);
+    bytes.push(0);
+    let idx = match labels.strings.iter().position(|string| *string == bytes) {
+        Some(idx) => idx,
+        _ => {
+            labels.strings.push(bytes);
+            labels.strings.len() - 1
+        }
+    };
+    format!(".L.str.{}", idx)
+}
+
+// Evaluates a constant expression of a floating-point type,
+// rounding the value of each float operation to the precision of float.
+fn eval_float(node: &Node) -> f64 {
+    let value = match node.kind {
+        NodeKind::NdNum => node.val.parse().unwrap(),
+        NodeKind::NdOperator => {
+            let (left, right) = match (&node.left, &node.right) {
+                (Some(node_left), Some(node_right)) => {
+                    (eval_float(node_left), eval_float(node_right))
+                }
+                _ => {
+                    panic!("Expected operands of {}", node.val);
+                }
+            };
+            match &node.val[..] {
+                "+" => left + right,
+                "-" => left - right,
+                "*" => left * right,
+                "/" => left / right,
+                _ => {
+                    panic!("Unexpected operator: {}", node.val);
+                }
+            }
+        }
+        NodeKind::NdUnaryOperator => match &node.left {
+            Some(node_left) if node.val == "-" => -eval_float(node_left),
+            Some(node_left) => eval_float(node_left),
+            _ => {
+                panic!("Expected operand of {}", node.val);
+            }
+        },
+        NodeKind::NdConditional => match (&node.cond, &node.then, &node.els) {
+            (Some(node_cond), Some(node_then), Some(node_els)) => {
+                if eval(node_cond) != 0 {
+                    eval_float(node_then)
+                } else {
+                    eval_float(node_els)
+                }
+            }
+            _ => {
+                panic!("Expected operands of ?:");
+            }
+        },
+        NodeKind::NdCast => match &node.left {
+            Some(node_left) if is_floating(node_left) => eval_float(node_left),
+            Some(node_left) if is_unsigned(node_left) => eval(node_left) as u64 as f64,
+            Some(node_left) => eval(node_left) as f64,
+            _ => {
+                panic!("Expected operand of cast");
+            }
+        },
+        _ => {
+            panic!("Initializer element is not constant");
+        }
+    };
+    float_bits(node, value).0
+}
+
+// Rounds a value to the floating-point type of `node`, returning it with its bit pattern.
+fn float_bits(node: &Node, value: f64) -> (f64, u64) {
+    if size_of(node) == 4 {
+        let value = value as f32;
+        (value as f64, value.to_bits() as u64)
+    } else {
+        (value, value.to_bits())
+    }
+}
+
+// Converts a value to the type of `node` the same way as CAST and CASTU do.
+fn truncate(node: &Node, value: i64) -> i64 {
+    match (size_of(node), is_unsigned(node)) {
+        (1, false) => value as i8 as i64,
+        (1, true) => value as u8 as i64,
+        (4, false) => value as i32 as i64,
+        (4, true) => value as u32 as i64,
+        _ => value,
+    }
+}
+
+// Collects the case labels of a switch statement in the order they appear,
+// skipping those that belong to a nested switch.
+fn collect_cases<'a>(node: &'a Node, cases: &mut Vec<&'a Node>) {
+    match node.kind {
+        NodeKind::NdSwitch => {}
+        NodeKind::NdCase => {
+            cases.push(node);
+            if let Some(node_then) = &node.then {
+                collect_cases(node_then, cases);
+            }
+        }
+        _ => {
+            if let Some(node_then) = &node.then {
+                collect_cases(node_then, cases);
+            }
+            if let Some(node_els) = &node.els {
+                collect_cases(node_els, cases);
+            }
+            for child in node.body.iter() {
+                collect_cases(child, cases);
             }
         }
-        match &node.right {
-            Some(node_right) => {
-                traverse(&node_right, mid_commands);
+    }
+}
+
+fn traverse(node: &Node, mid_commands: &mut Vec<String>, labels: &mut LabelContext) {
+    if let NodeKind::NdAssignOperator = node.kind {
+        match &node.left {
+            Some(node_left)
+                if matches!(node_left.kind, NodeKind::NdVariable) && !is_aggregate(node_left) =>
+            {
+                if let Some(node_right) = &node.right {
+                    traverse(node_right, mid_commands, labels);
+                }
+                mid_commands.push(format!("STORE {} {}", node_left.val, size_of(node_left)));
+            }
+            Some(node_left) => {
+                // any other lvalue is assigned through its address
+                traverse_address(node_left, mid_commands, labels);
+                if let Some(node_right) = &node.right {
+                    traverse(node_right, mid_commands, labels);
+                }
+                // a struct is copied from the address that is its value
+                if is_aggregate(node_left) {
+                    mid_commands.push(format!("COPY {}", size_of(node_left)));
+                } else {
+                    mid_commands.push(format!("ASSIGN {}", size_of(node_left)));
+                }
+            }
+            _ => {
+                panic!("Expected lvalue to the left of =");
             }
-            _ => {}
         }
-        mid_commands.push(String::from(format!("STORE {}", variable_name)));
         return;
     }
 
-    match &node.left {
-        Some(node_left) => {
-            traverse(&node_left, mid_commands);
+    if let NodeKind::NdLogicalOperator = node.kind {
+        // the right operand is evaluated only if the left one does not decide the result
+        let label_idx = labels.count;
+        labels.count += 1;
+        let (jump, label, value) = match &node.val[..] {
+            "&&" => ("JUMPZERO", format!(".L.false.{}", label_idx), 0),
+            _ => ("JUMPNONZERO", format!(".L.true.{}", label_idx), 1),
+        };
+        if let Some(node_left) = &node.left {
+            traverse(node_left, mid_commands, labels);
+        }
+        mid_commands.push(format!("{} {}", jump, label));
+        if let Some(node_right) = &node.right {
+            traverse(node_right, mid_commands, labels);
+        }
+        mid_commands.push(format!("{} {}", jump, label));
+        mid_commands.push(format!("PUSH {}", 1 - value));
+        mid_commands.push(format!("JUMP .L.end.{}", label_idx));
+        mid_commands.push(format!("LABEL {}", label));
+        mid_commands.push(format!("PUSH {}", value));
+        mid_commands.push(format!("LABEL .L.end.{}", label_idx));
+        return;
+    }
+
+    if let NodeKind::NdConditional = node.kind {
+        // only one of the arms is evaluated, and either leaves its value on the stack
+        let label_idx = labels.count;
+        labels.count += 1;
+        if let Some(node_cond) = &node.cond {
+            traverse(node_cond, mid_commands, labels);
+        }
+        mid_commands.push(format!("JUMPZERO .L.else.{}", label_idx));
+        if let Some(node_then) = &node.then {
+            traverse(node_then, mid_commands, labels);
+        }
+        mid_commands.push(format!("JUMP .L.end.{}", label_idx));
+        mid_commands.push(format!("LABEL .L.else.{}", label_idx));
+        if let Some(node_els) = &node.els {
+            traverse(node_els, mid_commands, labels);
         }
-        _ => {}
+        mid_commands.push(format!("LABEL .L.end.{}", label_idx));
+        return;
+    }
+
+    if let NodeKind::NdComma = node.kind {
+        // the value of the left operand is discarded
+        if let Some(node_left) = &node.left {
+            traverse(node_left, mid_commands, labels);
+        }
+        mid_commands.push(String::from("POP"));
+        if let Some(node_right) = &node.right {
+            traverse(node_right, mid_commands, labels);
+        }
+        return;
     }
 
-    match &node.right {
-        Some(node_right) => {
-            traverse(&node_right, mid_commands);
+    if let NodeKind::NdMember = node.kind {
+        traverse_address(node, mid_commands, labels);
+        if !is_aggregate(node) {
+            mid_commands.push(format!("DEREF{} {}", unsigned_suffix(node), size_of(node)));
         }
-        _ => {}
+        return;
+    }
+
+    if let NodeKind::NdAddress = node.kind {
+        if let Some(node_left) = &node.left {
+            traverse_address(node_left, mid_commands, labels);
+        }
+        return;
+    }
+
+    if let NodeKind::NdIf = node.kind {
+        let label_idx = labels.count;
+        labels.count += 1;
+        if let Some(node_cond) = &node.cond {
+            traverse(node_cond, mid_commands, labels);
+        }
+        mid_commands.push(format!("JUMPZERO .L.else.{}", label_idx));
+        if let Some(node_then) = &node.then {
+            traverse(node_then, mid_commands, labels);
+        }
+        mid_commands.push(format!("JUMP .L.end.{}", label_idx));
+        mid_commands.push(format!("LABEL .L.else.{}", label_idx));
+        if let Some(node_els) = &node.els {
+            traverse(node_els, mid_commands, labels);
+        }
+        mid_commands.push(format!("LABEL .L.end.{}", label_idx));
+        return;
+    }
+
+    if let NodeKind::NdFunction = node.kind {
+        if node.storage_class == StorageClass::ScStatic {
+            mid_commands.push(format!("LOCAL {}", node.val));
+        }
+        match node.fixed_arg_count {
+            Some(_) => mid_commands.push(format!("FUNCTION {} ...", node.val)),
+            _ => mid_commands.push(format!("FUNCTION {}", node.val)),
+        }
+        for param in node.params.iter() {
+            mid_commands.push(format!(
+                "PARAM {} {} {}",
+                param.val,
+                size_of(param),
+                format_of(param)
+            ));
+        }
+        labels.function_name = node.val.clone();
+        labels.defined_labels.clear();
+        labels.goto_labels.clear();
+        for node_ in node.body.iter() {
+            traverse(node_, mid_commands, labels);
+        }
+        // a goto may jump forward, so its label is checked once the whole function is seen
+        for label in labels.goto_labels.iter() {
+            if !labels.defined_labels.contains(label) {
+                panic!("Label {} used but not defined in {}", label, node.val);
+            }
+        }
+        mid_commands.push(String::from("FUNCTIONEND"));
+        return;
+    }
+
+    if let NodeKind::NdCall = node.kind {
+        // arguments are pushed from right to left so that the first one ends up on the top
+        for arg in node.args.iter().rev() {
+            traverse(arg, mid_commands, labels);
+        }
+        // the formats of the return value and the arguments choose their registers,
+        // and a function pointer is pushed last to be called by its address
+        let mut command = match &node.left {
+            Some(node_left) => {
+                traverse(node_left, mid_commands, labels);
+                format!("CALLPOINTER {} {}", node.args.len(), abi_format(node))
+            }
+            _ => format!("CALL {} {} {}", node.val, node.args.len(), abi_format(node)),
+        };
+        // the arguments matched by `...` are passed in the integer registers whatever their types
+        let fixed_arg_count = node.fixed_arg_count.unwrap_or(node.args.len());
+        for (i, arg) in node.args.iter().enumerate() {
+            let format = match abi_format(arg) {
+                "s" | "d" if i >= fixed_arg_count => "l",
+                format => format,
+            };
+            command.push(' ');
+            command.push_str(format);
+        }
+        mid_commands.push(command);
+        extend_unsigned_int(node, mid_commands);
+        return;
+    }
+
+    if let NodeKind::NdBlock = node.kind {
+        mid_commands.push(String::from("SCOPEBEGIN"));
+        for node_ in node.body.iter() {
+            traverse(node_, mid_commands, labels);
+        }
+        mid_commands.push(String::from("SCOPEEND"));
+        return;
+    }
+
+    if let NodeKind::NdDeclaration = node.kind {
+        for declarator in node
+            .body
+            .iter()
+            .filter(|declarator| !is_function(declarator))
+        {
+            match node.storage_class {
+                StorageClass::ScStatic => {
+                    let mut static_commands = Vec::new();
+                    declare_global_variable(node, declarator, &mut static_commands, labels);
+                    labels.statics.append(&mut static_commands);
+                    continue;
+                }
+                StorageClass::ScExtern => {
+                    declare_global_variable(node, declarator, mid_commands, labels);
+                    continue;
+                }
+                StorageClass::ScNone => {}
+            }
+            mid_commands.push(format!(
+                "DECLARE {} {} {}",
+                declarator.val,
+                size_of(declarator),
+                align_of(declarator)
+            ));
+            match &declarator.init {
+                Some(node_init) if matches!(node_init.kind, NodeKind::NdInitializer) => {
+                    initialize_local_variable(declarator, node_init, mid_commands, labels);
+                }
+                Some(node_init) => {
+                    traverse(node_init, mid_commands, labels);
+                }
+                _ => {}
+            }
+        }
+        return;
+    }
+
+    if let NodeKind::NdSwitch = node.kind {
+        let label_idx = labels.count;
+        labels.count += 1;
+        let node_cond = match &node.cond {
+            Some(node_cond) => {
+                traverse(node_cond, mid_commands, labels);
+                node_cond
+            }
+            _ => {
+                panic!("Expected switch quantity");
+            }
+        };
+        let mut cases = Vec::new();
+        if let Some(node_then) = &node.then {
+            collect_cases(node_then, &mut cases);
+        }
+        // without a default label, an unmatched value leaves the switch
+        let mut default_label = format!(".L.break.{}", label_idx);
+        let mut values = Vec::new();
+        let mut command = String::new();
+        for case in cases.iter() {
+            let label = format!(".L.case.{}", case.val);
+            match &case.left {
+                Some(node_left) => {
+                    // the value is converted to the type of the switch quantity
+                    let value = truncate(node_cond, eval(node_left));
+                    if values.contains(&value) {
+                        panic!("Duplicate case value {}", value);
+                    }
+                    values.push(value);
+                    command.push_str(&format!(" {} {}", value, label));
+                }
+                _ if default_label.starts_with(".L.case.") => {
+                    panic!("Multiple default labels in one switch");
+                }
+                _ => {
+                    default_label = label;
+                }
+            }
+        }
+        mid_commands.push(format!("SWITCH {} {}{}", label_idx, default_label, command));
+        labels.break_labels.push(format!(".L.break.{}", label_idx));
+        labels.switches += 1;
+        if let Some(node_then) = &node.then {
+            traverse(node_then, mid_commands, labels);
+        }
+        labels.switches -= 1;
+        labels.break_labels.pop();
+        mid_commands.push(format!("LABEL .L.break.{}", label_idx));
+        return;
+    }
+
+    if let NodeKind::NdLabel = node.kind {
+        if labels.defined_labels.contains(&node.val) {
+            panic!("Duplicate label {} in {}", node.val, labels.function_name);
+        }
+        labels.defined_labels.push(node.val.clone());
+        mid_commands.push(format!(
+            "LABEL .L.label.{}.{}",
+            labels.function_name, node.val
+        ));
+        if let Some(node_then) = &node.then {
+            traverse(node_then, mid_commands, labels);
+        }
+        return;
+    }
+
+    if let NodeKind::NdDoWhile = node.kind {
+        let label_idx = labels.count;
+        labels.count += 1;
+        mid_commands.push(format!("LABEL .L.begin.{}", label_idx));
+        labels.break_labels.push(format!(".L.break.{}", label_idx));
+        labels
+            .continue_labels
+            .push(format!(".L.continue.{}", label_idx));
+        if let Some(node_then) = &node.then {
+            traverse(node_then, mid_commands, labels);
+        }
+        labels.break_labels.pop();
+        labels.continue_labels.pop();
+        mid_commands.push(format!("LABEL .L.continue.{}", label_idx));
+        if let Some(node_cond) = &node.cond {
+            traverse(node_cond, mid_commands, labels);
+        }
+        mid_commands.push(format!("JUMPNONZERO .L.begin.{}", label_idx));
+        mid_commands.push(format!("LABEL .L.break.{}", label_idx));
+        return;
+    }
+
+    if let NodeKind::NdCase = node.kind {
+        if labels.switches == 0 {
+            panic!("case label not within a switch statement");
+        }
+        mid_commands.push(format!("LABEL .L.case.{}", node.val));
+        if let Some(node_then) = &node.then {
+            traverse(node_then, mid_commands, labels);
+        }
+        return;
+    }
+
+    if let NodeKind::NdWhile | NodeKind::NdFor = node.kind {
+        let label_idx = labels.count;
+        labels.count += 1;
+        // a variable declared in the initialization of for is visible only inside the loop
+        let has_scope = matches!(&node.init, Some(node_init) if matches!(node_init.kind, NodeKind::NdDeclaration));
+        if has_scope {
+            mid_commands.push(String::from("SCOPEBEGIN"));
+        }
+        if let Some(node_init) = &node.init {
+            traverse(node_init, mid_commands, labels);
+        }
+        mid_commands.push(format!("LABEL .L.begin.{}", label_idx));
+        // a missing condition (`for (;;)`) loops forever
+        if let Some(node_cond) = &node.cond {
+            traverse(node_cond, mid_commands, labels);
+            mid_commands.push(format!("JUMPZERO .L.break.{}", label_idx));
+        }
+        labels.break_labels.push(format!(".L.break.{}", label_idx));
+        labels
+            .continue_labels
+            .push(format!(".L.continue.{}", label_idx));
+        if let Some(node_then) = &node.then {
+            traverse(node_then, mid_commands, labels);
+        }
+        labels.break_labels.pop();
+        labels.continue_labels.pop();
+        mid_commands.push(format!("LABEL .L.continue.{}", label_idx));
+        if let Some(node_inc) = &node.inc {
+            traverse(node_inc, mid_commands, labels);
+        }
+        mid_commands.push(format!("JUMP .L.begin.{}", label_idx));
+        mid_commands.push(format!("LABEL .L.break.{}", label_idx));
+        if has_scope {
+            mid_commands.push(String::from("SCOPEEND"));
+        }
+        return;
+    }
+
+    if let Some(node_left) = &node.left {
+        traverse(node_left, mid_commands, labels);
+    }
+
+    if let Some(node_right) = &node.right {
+        traverse(node_right, mid_commands, labels);
     }
 
     match node.kind {
+        NodeKind::NdNum if is_floating(node) => {
+            // a floating-point constant is loaded from its bit pattern in .rodata
+            let (_, bits) = float_bits(node, node.val.parse().unwrap());
+            let constant = (size_of(node), bits);
+            let idx = match labels.floats.iter().position(|float| *float == constant) {
+                Some(idx) => idx,
+                _ => {
+                    labels.floats.push(constant);
+                    labels.floats.len() - 1
+                }
+            };
+            mid_commands.push(format!("LOAD .L.float.{} {}", idx, size_of(node)));
+        }
         NodeKind::NdNum => {
             mid_commands.push(format!("PUSH {}", node.val));
         }
-        NodeKind::NdOperator => match &node.val[..] {
+        NodeKind::NdOperator if is_floating(node) => match &node.val[..] {
             "+" => {
-                mid_commands.push(String::from("ADD"));
+                mid_commands.push(format!("FADD {}", format_of(node)));
             }
             "-" => {
-                mid_commands.push(String::from("SUB"));
+                mid_commands.push(format!("FSUB {}", format_of(node)));
             }
             "*" => {
-                mid_commands.push(String::from("MUL"));
+                mid_commands.push(format!("FMUL {}", format_of(node)));
             }
             "/" => {
-                mid_commands.push(String::from("DIV"));
+                mid_commands.push(format!("FDIV {}", format_of(node)));
             }
             _ => {
                 panic!("Unexpected operator: {}", node.val);
             }
         },
+        NodeKind::NdOperator => {
+            match &node.val[..] {
+                "+" => {
+                    mid_commands.push(format!("ADD {}", size_of(node)));
+                }
+                "-" => {
+                    mid_commands.push(format!("SUB {}", size_of(node)));
+                }
+                "*" => {
+                    mid_commands.push(format!("MUL {}", size_of(node)));
+                }
+                "/" => {
+                    mid_commands.push(format!("DIV{} {}", unsigned_suffix(node), size_of(node)));
+                }
+                "%" => {
+                    mid_commands.push(format!("MOD{} {}", unsigned_suffix(node), size_of(node)));
+                }
+                "&" => {
+                    mid_commands.push(String::from("AND"));
+                }
+                "|" => {
+                    mid_commands.push(String::from("OR"));
+                }
+                "^" => {
+                    mid_commands.push(String::from("XOR"));
+                }
+                "<<" => {
+                    mid_commands.push(format!("SHL {}", size_of(node)));
+                }
+                ">>" => {
+                    mid_commands.push(format!("SHR{} {}", unsigned_suffix(node), size_of(node)));
+                }
+                _ => {
+                    panic!("Unexpected operator: {}", node.val);
+                }
+            }
+            extend_unsigned_int(node, mid_commands);
+        }
+        NodeKind::NdComparisonOperator if matches!(&node.left, Some(node_left) if is_floating(node_left)) =>
+        {
+            let format = match &node.left {
+                Some(node_left) => format_of(node_left),
+                _ => "d",
+            };
+            let command = match &node.val[..] {
+                "==" => "FEQUAL",
+                "!=" => "FNONEQUAL",
+                "<" => "FLT",
+                "<=" => "FELT",
+                _ => {
+                    panic!("Unexpected operator: {}", node.val);
+                }
+            };
+            mid_commands.push(format!("{} {}", command, format));
+        }
         NodeKind::NdComparisonOperator => match &node.val[..] {
             "==" => {
                 mid_commands.push(String::from("EQUAL"));
@@ -81,11 +1001,12 @@ fn traverse(node: &Node, mid_commands: &mut Vec<String>) {
             "!=" => {
                 mid_commands.push(String::from("NONEQUAL"));
             }
+            // the operands have the same type (or are pointers), so either tells the comparison
             "<" => {
-                mid_commands.push(String::from("LT"));
+                mid_commands.push(format!("LT{}", operand_unsigned_suffix(node)));
             }
             "<=" => {
-                mid_commands.push(String::from("ELT"));
+                mid_commands.push(format!("ELT{}", operand_unsigned_suffix(node)));
             }
             _ => {
                 panic!("Unexpected operator: {}", node.val);
@@ -95,10 +1016,247 @@ fn traverse(node: &Node, mid_commands: &mut Vec<String>) {
             // Do nothing because it is previously processed.
         }
         NodeKind::NdVariable => {
-            mid_commands.push(String::from(format!("LOAD {}", node.val)));
+            // the value of an array is the address of its first element
+            // and the value of a struct is its address
+            if is_aggregate(node) {
+                mid_commands.push(format!("ADDRESS {}", node.val));
+            } else {
+                mid_commands.push(format!(
+                    "LOAD{} {} {}",
+                    unsigned_suffix(node),
+                    node.val,
+                    size_of(node)
+                ));
+            }
         }
         NodeKind::NdReturn => {
-            mid_commands.push(String::from(format!("RETURN")));
+            // a function returning void has no value to return
+            match &node.left {
+                Some(node_left) => mid_commands.push(format!("RETURN {}", abi_format(node_left))),
+                _ => mid_commands.push(String::from("RETURN")),
+            }
+        }
+        NodeKind::NdExpressionStatement => {
+            // An empty statement (a lone `;`) leaves nothing to discard.
+            if node.left.is_some() {
+                mid_commands.push(String::from("POP"));
+            }
+        }
+        NodeKind::NdBreak => match labels.break_labels.last() {
+            Some(label) => {
+                mid_commands.push(format!("JUMP {}", label));
+            }
+            _ => {
+                panic!("break statement not within a loop");
+            }
+        },
+        NodeKind::NdGoto => {
+            labels.goto_labels.push(node.val.clone());
+            mid_commands.push(format!(
+                "JUMP .L.label.{}.{}",
+                labels.function_name, node.val
+            ));
+        }
+        NodeKind::NdContinue => match labels.continue_labels.last() {
+            Some(label) => {
+                mid_commands.push(format!("JUMP {}", label));
+            }
+            _ => {
+                panic!("continue statement not within a loop");
+            }
+        },
+        NodeKind::NdIf
+        | NodeKind::NdWhile
+        | NodeKind::NdFor
+        | NodeKind::NdBlock
+        | NodeKind::NdDeclaration
+        | NodeKind::NdFunction
+        | NodeKind::NdCall => {
+            // Do nothing because it is previously processed.
+        }
+        NodeKind::NdAddress
+        | NodeKind::NdMember
+        | NodeKind::NdLogicalOperator
+        | NodeKind::NdComma
+        | NodeKind::NdConditional
+        | NodeKind::NdSwitch
+        | NodeKind::NdCase
+        | NodeKind::NdDoWhile
+        | NodeKind::NdLabel
+        | NodeKind::NdTypedef
+        | NodeKind::NdInitializer
+        | NodeKind::NdDesignator => {
+            // Do nothing because it is previously processed.
+        }
+        NodeKind::NdBitwiseNot => {
+            mid_commands.push(String::from("NOT"));
+            extend_unsigned_int(node, mid_commands);
+        }
+        // negating a floating-point number flips its sign, so `-0.0` is not `0.0 - 0.0`
+        NodeKind::NdUnaryOperator if node.val == "-" && is_floating(node) => {
+            mid_commands.push(format!("FNEG {}", format_of(node)));
+        }
+        NodeKind::NdUnaryOperator if node.val == "-" => {
+            mid_commands.push(format!("NEG {}", size_of(node)));
+            extend_unsigned_int(node, mid_commands);
+        }
+        NodeKind::NdUnaryOperator => {
+            // `+x` is the value of x
+        }
+        NodeKind::NdSizeof | NodeKind::NdPostfixOperator => {
+            // Do nothing because it is replaced by the type checker.
+        }
+        NodeKind::NdVaStart => {
+            mid_commands.push(String::from("VASTART"));
+        }
+        NodeKind::NdString => {
+            mid_commands.push(format!("ADDRESS {}", string_label(node, labels)));
+        }
+        NodeKind::NdDereference => {
+            if !is_aggregate(node) {
+                mid_commands.push(format!("DEREF{} {}", unsigned_suffix(node), size_of(node)));
+            }
+        }
+        NodeKind::NdFunctionDeclaration => {
+            // A function defined elsewhere is resolved by the linker when its address is taken,
+            // but a static one is defined later in this file.
+            if node.storage_class == StorageClass::ScStatic {
+                mid_commands.push(format!("LOCAL {}", node.val));
+            } else {
+                mid_commands.push(format!("EXTERN {}", node.val));
+            }
+        }
+        NodeKind::NdCast => {
+            // a conversion from or to a floating-point number changes its format
+            if let Some(node_left) = &node.left {
+                if is_floating(node) || is_floating(node_left) {
+                    let (to, from) = (conversion_format(node), conversion_format(node_left));
+                    if to != from {
+                        mid_commands.push(format!("FCAST {} {}", to, from));
+                    }
+                }
+            }
+            // values on the stack are always kept extended to 64 bits,
+            // so only a conversion to a narrower integer needs code
+            if let Some(ty) = &node.ty {
+                if ty.is_integer() && ty.size < 8 {
+                    mid_commands.push(format!("CAST{} {}", unsigned_suffix(node), ty.size));
+                }
+            }
         }
     }
 }
+
+// Pushes the address of an lvalue instead of its value.
+fn traverse_address(node: &Node, mid_commands: &mut Vec<String>, labels: &mut LabelContext) {
+    match node.kind {
+        NodeKind::NdVariable => {
+            mid_commands.push(format!("ADDRESS {}", node.val));
+        }
+        NodeKind::NdDereference => {
+            // the address of `*p` is the value of `p`
+            if let Some(node_left) = &node.left {
+                traverse(node_left, mid_commands, labels);
+            }
+        }
+        NodeKind::NdMember => {
+            if let Some(node_left) = &node.left {
+                traverse_address(node_left, mid_commands, labels);
+            }
+            mid_commands.push(format!("MEMBER {}", node.offset));
+        }
+        // the value of a struct (e.g. chosen by ?: or assigned) is already its address
+        _ if is_aggregate(node) => {
+            traverse(node, mid_commands, labels);
+        }
+        _ => {
+            panic!("Expected lvalue");
+        }
+    }
+}
+
+// Unsigned values (and addresses) are loaded, divided, shifted and compared
+// by the commands with the suffix U.
+fn is_unsigned(node: &Node) -> bool {
+    matches!(&node.ty, Some(ty) if ty.is_unsigned || ty.is_pointer())
+}
+
+fn unsigned_suffix(node: &Node) -> &'static str {
+    if is_unsigned(node) {
+        "U"
+    } else {
+        ""
+    }
+}
+
+fn operand_unsigned_suffix(node: &Node) -> &'static str {
+    match &node.left {
+        Some(node_left) if is_unsigned(node_left) || is_aggregate(node_left) => "U",
+        _ => "",
+    }
+}
+
+// The 32-bit operations sign-extend their results, so an unsigned int is zero-extended again.
+fn extend_unsigned_int(node: &Node, mid_commands: &mut Vec<String>) {
+    if is_unsigned(node) && size_of(node) == 4 {
+        mid_commands.push(String::from("CASTU 4"));
+    }
+}
+
+fn is_floating(node: &Node) -> bool {
+    matches!(&node.ty, Some(ty) if ty.is_floating())
+}
+
+// The format of a value decides the registers it is passed in:
+// s (float) and d (double) use the floating-point registers, and l the integer registers.
+fn format_of(node: &Node) -> &'static str {
+    match &node.ty {
+        Some(ty) if matches!(ty.kind, TypeKind::TyFloat) => "s",
+        Some(ty) if matches!(ty.kind, TypeKind::TyDouble) => "d",
+        _ => "l",
+    }
+}
+
+// An unsigned int is kept zero-extended on the stack, but the psABI passes and returns it
+// sign-extended like an int, so its format is w at a call and a return.
+fn abi_format(node: &Node) -> &'static str {
+    match format_of(node) {
+        "l" if is_unsigned(node) && size_of(node) == 4 => "w",
+        format => format,
+    }
+}
+
+// the formats of the conversions, where an unsigned integer is lu
+fn conversion_format(node: &Node) -> &'static str {
+    match format_of(node) {
+        "l" if is_unsigned(node) => "lu",
+        format => format,
+    }
+}
+
+fn size_of(node: &Node) -> usize {
+    match &node.ty {
+        Some(ty) => ty.size,
+        _ => {
+            panic!("Type of {} is not determined", node.val);
+        }
+    }
+}
+
+fn align_of(node: &Node) -> usize {
+    match &node.ty {
+        Some(ty) => ty.align,
+        _ => {
+            panic!("Type of {} is not determined", node.val);
+        }
+    }
+}
+
+// the value of an array, a struct, a union or a function is its address
+fn is_aggregate(node: &Node) -> bool {
+    matches!(&node.ty, Some(ty) if ty.is_array() || ty.is_struct() || ty.is_function())
+}
+
+fn is_function(node: &Node) -> bool {
+    matches!(&node.ty, Some(ty) if ty.is_function())
+}
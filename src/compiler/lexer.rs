@@ -1,11 +1,40 @@
 use crate::compiler::*;
 
-pub fn lexing(input: &String) -> Vec<Token> {
+pub fn lexing(input: &str) -> Vec<Token> {
     let mut tokens: Vec<Token> = Vec::new();
 
     let mut begin_idx = 0;
     let mut condition = LexerCondition::CondCompletion;
-    for (i, s) in input.chars().enumerate() {
+    let mut is_escaped = false;
+    for (i, s) in input.char_indices() {
+        // any character can appear in a literal, which ends at the first unescaped quote
+        if let LexerCondition::CondMiddleOfString | LexerCondition::CondMiddleOfCharacter =
+            condition
+        {
+            if is_escaped {
+                is_escaped = false;
+            } else if s == '\\' {
+                is_escaped = true;
+            } else if s == '"' && matches!(condition, LexerCondition::CondMiddleOfString) {
+                let new_token = Token {
+                    kind: TokenKind::TkString,
+                    val: input[begin_idx..i].to_string(),
+                };
+                tokens.push(new_token);
+                begin_idx = i + 1;
+                condition = LexerCondition::CondCompletion;
+            } else if s == '\'' && matches!(condition, LexerCondition::CondMiddleOfCharacter) {
+                // a character literal is just another way to write an integer
+                let new_token = Token {
+                    kind: TokenKind::TkNum,
+                    val: character_value(&input[begin_idx..i]).to_string(),
+                };
+                tokens.push(new_token);
+                begin_idx = i + 1;
+                condition = LexerCondition::CondCompletion;
+            }
+            continue;
+        }
         match return_letter_kind(s) {
             LetterKind::LtNum => match condition {
                 LexerCondition::CondMiddleOfNumber => {}
@@ -19,9 +48,7 @@ pub fn lexing(input: &String) -> Vec<Token> {
                     condition = LexerCondition::CondMiddleOfNumber;
                 }
                 LexerCondition::CondMiddleOfVariable => {
-                    tokens.push(create_token_of_variable(&input[begin_idx..i]));
-                    begin_idx = i;
-                    condition = LexerCondition::CondMiddleOfNumber;
+                    // digits may follow the first letter of a name (e.g. `add10`)
                 }
                 _ => {
                     begin_idx = i;
@@ -56,19 +83,36 @@ pub fn lexing(input: &String) -> Vec<Token> {
                     begin_idx = i + 1;
                 }
             },
-            LetterKind::LtOperator | LetterKind::LtParenthesis | LetterKind::LtSymbol => {
-                let new_tokenkind;
-                match return_letter_kind(s) {
-                    LetterKind::LtParenthesis => {
-                        new_tokenkind = TokenKind::TkParenthesis;
-                    }
-                    LetterKind::LtSymbol => {
-                        new_tokenkind = TokenKind::TkSymbol;
-                    }
-                    _ => {
-                        new_tokenkind = TokenKind::TkOperator;
-                    }
+            // a decimal point continues a number or begins one (e.g. `.5`),
+            // and so does the sign of an exponent (e.g. `1e-3`)
+            LetterKind::LtSymbol
+                if s == '.'
+                    && (matches!(condition, LexerCondition::CondMiddleOfNumber)
+                        || !matches!(condition, LexerCondition::CondMiddleOfVariable)
+                            && input[i + 1..].starts_with(|c: char| c.is_ascii_digit())) =>
+            {
+                if let LexerCondition::CondMiddleOfComparisonOperator = condition {
+                    let new_token = Token {
+                        kind: TokenKind::TkComparisonOperator,
+                        val: check_valid_token(&input[begin_idx..i]).to_string(),
+                    };
+                    tokens.push(new_token);
+                }
+                if !matches!(condition, LexerCondition::CondMiddleOfNumber) {
+                    begin_idx = i;
+                    condition = LexerCondition::CondMiddleOfNumber;
                 }
+            }
+            LetterKind::LtOperator
+                if matches!(s, '+' | '-')
+                    && matches!(condition, LexerCondition::CondMiddleOfNumber)
+                    && input[..i].ends_with(&['e', 'E'][..]) => {}
+            LetterKind::LtOperator | LetterKind::LtParenthesis | LetterKind::LtSymbol => {
+                let new_tokenkind = match return_letter_kind(s) {
+                    LetterKind::LtParenthesis => TokenKind::TkParenthesis,
+                    LetterKind::LtSymbol => TokenKind::TkSymbol,
+                    _ => TokenKind::TkOperator,
+                };
                 match condition {
                     LexerCondition::CondMiddleOfNumber => {
                         let new_token = Token {
@@ -78,7 +122,7 @@ pub fn lexing(input: &String) -> Vec<Token> {
                         tokens.push(new_token);
                         let new_token = Token {
                             kind: new_tokenkind,
-                            val: String::from(s.to_string()),
+                            val: s.to_string(),
                         };
                         tokens.push(new_token);
                         begin_idx = i + 1;
@@ -92,7 +136,7 @@ pub fn lexing(input: &String) -> Vec<Token> {
                         tokens.push(new_token);
                         let new_token = Token {
                             kind: new_tokenkind,
-                            val: String::from(s.to_string()),
+                            val: s.to_string(),
                         };
                         tokens.push(new_token);
                         begin_idx = i + 1;
@@ -102,16 +146,19 @@ pub fn lexing(input: &String) -> Vec<Token> {
                         tokens.push(create_token_of_variable(&input[begin_idx..i]));
                         let new_token = Token {
                             kind: new_tokenkind,
-                            val: String::from(s.to_string()),
+                            val: s.to_string(),
                         };
                         tokens.push(new_token);
                         begin_idx = i + 1;
                         condition = LexerCondition::CondCompletion;
                     }
+                    _ if join_operator(&mut tokens, input, i, s) => {
+                        begin_idx = i + 1;
+                    }
                     _ => {
                         let new_token = Token {
                             kind: new_tokenkind,
-                            val: String::from(s.to_string()),
+                            val: s.to_string(),
                         };
                         tokens.push(new_token);
                         begin_idx = i + 1;
@@ -128,26 +175,73 @@ pub fn lexing(input: &String) -> Vec<Token> {
                     begin_idx = i;
                     condition = LexerCondition::CondMiddleOfComparisonOperator;
                 }
-                LexerCondition::CondMiddleOfComparisonOperator => {
+                LexerCondition::CondMiddleOfComparisonOperator
+                    if is_valid_token(&input[begin_idx..i + 1]) =>
+                {
                     let new_token = Token {
                         kind: TokenKind::TkComparisonOperator,
-                        val: check_valid_token(&input[begin_idx..i + 1]).to_string(),
+                        val: input[begin_idx..i + 1].to_string(),
                     };
                     tokens.push(new_token);
                     begin_idx = i;
                     condition = LexerCondition::CondCompletion;
                 }
+                LexerCondition::CondMiddleOfComparisonOperator => {
+                    // the character cannot extend the operator, so it begins the next one (e.g. `!!x`)
+                    let new_token = Token {
+                        kind: TokenKind::TkComparisonOperator,
+                        val: check_valid_token(&input[begin_idx..i]).to_string(),
+                    };
+                    tokens.push(new_token);
+                    begin_idx = i;
+                }
                 LexerCondition::CondMiddleOfVariable => {
                     tokens.push(create_token_of_variable(&input[begin_idx..i]));
                     begin_idx = i;
                     condition = LexerCondition::CondMiddleOfComparisonOperator;
                 }
+                _ if join_operator(&mut tokens, input, i, s) => {
+                    begin_idx = i + 1;
+                }
                 _ => {
                     begin_idx = i;
                     condition = LexerCondition::CondMiddleOfComparisonOperator;
                 }
             },
+            LetterKind::LtQuote => {
+                match condition {
+                    LexerCondition::CondMiddleOfNumber => {
+                        let new_token = Token {
+                            kind: TokenKind::TkNum,
+                            val: input[begin_idx..i].to_string(),
+                        };
+                        tokens.push(new_token);
+                    }
+                    LexerCondition::CondMiddleOfComparisonOperator => {
+                        let new_token = Token {
+                            kind: TokenKind::TkComparisonOperator,
+                            val: check_valid_token(&input[begin_idx..i]).to_string(),
+                        };
+                        tokens.push(new_token);
+                    }
+                    LexerCondition::CondMiddleOfVariable => {
+                        tokens.push(create_token_of_variable(&input[begin_idx..i]));
+                    }
+                    _ => {}
+                }
+                // the literal begins after the quote
+                begin_idx = i + 1;
+                condition = if s == '"' {
+                    LexerCondition::CondMiddleOfString
+                } else {
+                    LexerCondition::CondMiddleOfCharacter
+                };
+            }
             LetterKind::LtAlphabet => match condition {
+                // the suffixes of an integer literal (e.g. `10u` or `10UL`) are a part of it,
+                // and so are the exponent and the suffix of a floating-point literal (e.g. `1e3f`)
+                LexerCondition::CondMiddleOfNumber
+                    if matches!(s, 'u' | 'U' | 'l' | 'L' | 'e' | 'E' | 'f' | 'F') => {}
                 LexerCondition::CondMiddleOfNumber => {
                     let new_token = Token {
                         kind: TokenKind::TkNum,
@@ -176,46 +270,260 @@ pub fn lexing(input: &String) -> Vec<Token> {
             },
         }
     }
+    if let LexerCondition::CondMiddleOfString | LexerCondition::CondMiddleOfCharacter = condition {
+        panic!("Unterminated literal {}", &input[begin_idx..]);
+    }
     tokens
 }
 
 fn create_token_of_variable(s: &str) -> Token {
     match s {
-        "return" => {
-            return Token {
-                kind: TokenKind::TkReturn,
-                val: String::from("return"),
-            };
-        }
-        _ => {
-            return Token {
-                kind: TokenKind::TkVariable,
-                val: String::from(s),
-            };
-        }
+        "return" => Token {
+            kind: TokenKind::TkReturn,
+            val: String::from("return"),
+        },
+        "if" => Token {
+            kind: TokenKind::TkIf,
+            val: String::from("if"),
+        },
+        "else" => Token {
+            kind: TokenKind::TkElse,
+            val: String::from("else"),
+        },
+        "while" => Token {
+            kind: TokenKind::TkWhile,
+            val: String::from("while"),
+        },
+        "for" => Token {
+            kind: TokenKind::TkFor,
+            val: String::from("for"),
+        },
+        "break" => Token {
+            kind: TokenKind::TkBreak,
+            val: String::from("break"),
+        },
+        "continue" => Token {
+            kind: TokenKind::TkContinue,
+            val: String::from("continue"),
+        },
+        "void" => Token {
+            kind: TokenKind::TkVoid,
+            val: String::from("void"),
+        },
+        "int" => Token {
+            kind: TokenKind::TkInt,
+            val: String::from("int"),
+        },
+        "extern" => Token {
+            kind: TokenKind::TkExtern,
+            val: String::from("extern"),
+        },
+        "char" => Token {
+            kind: TokenKind::TkChar,
+            val: String::from("char"),
+        },
+        "long" => Token {
+            kind: TokenKind::TkLong,
+            val: String::from("long"),
+        },
+        "struct" => Token {
+            kind: TokenKind::TkStruct,
+            val: String::from("struct"),
+        },
+        "union" => Token {
+            kind: TokenKind::TkUnion,
+            val: String::from("union"),
+        },
+        "sizeof" => Token {
+            kind: TokenKind::TkSizeof,
+            val: String::from("sizeof"),
+        },
+        "switch" => Token {
+            kind: TokenKind::TkSwitch,
+            val: String::from("switch"),
+        },
+        "case" => Token {
+            kind: TokenKind::TkCase,
+            val: String::from("case"),
+        },
+        "default" => Token {
+            kind: TokenKind::TkDefault,
+            val: String::from("default"),
+        },
+        "do" => Token {
+            kind: TokenKind::TkDo,
+            val: String::from("do"),
+        },
+        "goto" => Token {
+            kind: TokenKind::TkGoto,
+            val: String::from("goto"),
+        },
+        "typedef" => Token {
+            kind: TokenKind::TkTypedef,
+            val: String::from("typedef"),
+        },
+        "enum" => Token {
+            kind: TokenKind::TkEnum,
+            val: String::from("enum"),
+        },
+        "signed" => Token {
+            kind: TokenKind::TkSigned,
+            val: String::from("signed"),
+        },
+        "unsigned" => Token {
+            kind: TokenKind::TkUnsigned,
+            val: String::from("unsigned"),
+        },
+        "float" => Token {
+            kind: TokenKind::TkFloat,
+            val: String::from("float"),
+        },
+        "double" => Token {
+            kind: TokenKind::TkDouble,
+            val: String::from("double"),
+        },
+        "static" => Token {
+            kind: TokenKind::TkStatic,
+            val: String::from("static"),
+        },
+        "const" => Token {
+            kind: TokenKind::TkConst,
+            val: String::from("const"),
+        },
+        "volatile" => Token {
+            kind: TokenKind::TkVolatile,
+            val: String::from("volatile"),
+        },
+        _ => Token {
+            kind: TokenKind::TkVariable,
+            val: String::from(s),
+        },
     }
 }
 
 fn return_letter_kind(s: char) -> LetterKind {
     match s {
         '0'..='9' => LetterKind::LtNum,
-        'a'..='z' => LetterKind::LtAlphabet,
+        'a'..='z' | 'A'..='Z' | '_' => LetterKind::LtAlphabet,
         ' ' => LetterKind::LtSpace,
-        '+' | '-' | '*' | '/' => LetterKind::LtOperator,
-        '(' | ')' => LetterKind::LtParenthesis,
-        ';' => LetterKind::LtSymbol,
+        '+' | '-' | '*' | '/' | '%' | '&' | '|' | '^' | '~' => LetterKind::LtOperator,
+        '(' | ')' | '{' | '}' | '[' | ']' => LetterKind::LtParenthesis,
+        ';' | ',' | '.' | '?' | ':' => LetterKind::LtSymbol,
         '<' | '>' | '=' | '!' => LetterKind::LtComparisonOperator,
+        '"' | '\'' => LetterKind::LtQuote,
         _ => {
             panic!("Cannot recognize {}", s);
         }
     }
 }
 
-fn check_valid_token(s: &str) -> &str {
-    match s {
-        "<" | ">" | "<=" | ">=" | "==" | "!=" | "=" => s,
+// Returns the bytes represented by the text of a string or character literal.
+pub fn decode_escapes(s: &str) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        let c = match chars.next() {
+            Some(c) => c,
+            _ => {
+                panic!("Unterminated escape sequence in {}", s);
+            }
+        };
+        match c {
+            // an octal escape has up to three digits (e.g. `\0`, `\101`)
+            '0'..='7' => {
+                let mut value = c.to_digit(8).unwrap();
+                for _ in 0..2 {
+                    match chars.peek().and_then(|c| c.to_digit(8)) {
+                        Some(digit) => {
+                            value = value * 8 + digit;
+                            chars.next();
+                        }
+                        _ => break,
+                    }
+                }
+                bytes.push(value as u8);
+            }
+            // a hexadecimal escape takes every following hex digit (e.g. `\x41`)
+            'x' => {
+                let mut value: u32 = 0;
+                let mut has_digit = false;
+                while let Some(digit) = chars.peek().and_then(|c| c.to_digit(16)) {
+                    value = value.wrapping_mul(16).wrapping_add(digit);
+                    has_digit = true;
+                    chars.next();
+                }
+                if !has_digit {
+                    panic!("Expected hexadecimal digits after \\x in {}", s);
+                }
+                bytes.push(value as u8);
+            }
+            'a' => bytes.push(7),
+            'b' => bytes.push(8),
+            't' => bytes.push(9),
+            'n' => bytes.push(10),
+            'v' => bytes.push(11),
+            'f' => bytes.push(12),
+            'r' => bytes.push(13),
+            'e' => bytes.push(27),
+            // the other characters stand for themselves (e.g. `\\`, `\'`, `\"`)
+            _ => {
+                let mut buf = [0; 4];
+                bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            }
+        }
+    }
+    bytes
+}
+
+// char is signed, so `'\xff'` is -1
+fn character_value(s: &str) -> i64 {
+    match decode_escapes(s)[..] {
+        [byte] => byte as i8 as i64,
         _ => {
-            panic!("Not a valid token : {}", s);
+            panic!("Invalid character literal '{}'", s);
+        }
+    }
+}
+
+// An operator character directly after an operator is joined to it if they form a longer one.
+fn join_operator(tokens: &mut [Token], input: &str, i: usize, s: char) -> bool {
+    match tokens.last_mut() {
+        Some(token)
+            if matches!(
+                token.kind,
+                TokenKind::TkOperator | TokenKind::TkComparisonOperator
+            ) && input[..i].ends_with(&token.val) =>
+        {
+            let joined = format!("{}{}", token.val, s);
+            if let "->" | "&&" | "||" | "++" | "--" | "+=" | "-=" | "*=" | "/=" | "%=" | "&="
+            | "|=" | "^=" | "<<=" | ">>=" = &joined[..]
+            {
+                token.val = joined;
+                true
+            } else {
+                false
+            }
         }
+        _ => false,
+    }
+}
+
+fn is_valid_token(s: &str) -> bool {
+    matches!(
+        s,
+        "<" | ">" | "<=" | ">=" | "==" | "!=" | "=" | "!" | "<<" | ">>"
+    )
+}
+
+fn check_valid_token(s: &str) -> &str {
+    if is_valid_token(s) {
+        s
+    } else {
+        panic!("Not a valid token : {}", s);
     }
 }
@@ -1,13 +1,103 @@
 // use crate::compiler::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-pub fn generate_native_code(mid_commands: &Vec<String>) -> Vec<String> {
+// Stack frame of the function being generated.
+// s0 points at the top of the frame, with ra at -8(s0) and the caller's s0 at -16(s0);
+// local variables are placed below them.
+// a variadic function saves a0-a7 at -64(s0) to -8(s0) instead, right below the arguments
+// passed on the caller's stack, and its ra and s0 are saved below that register save area.
+struct Frame<'a> {
+    // one map per lexical scope (innermost last), each mapping a variable to its offset from s0,
+    // or to None if the name refers to a global variable there (e.g. `extern int x;` in a block).
+    // a slot is released when its scope ends, so sibling scopes share the same part of the frame.
+    variable_maps: Vec<HashMap<&'a str, Option<usize>>>,
+    scope_offsets: Vec<usize>,
+    offset: usize,
+    max_offset: usize,
+}
+
+impl<'a> Frame<'a> {
+    // header_size is the size of the saved registers at the top of the frame
+    fn new(header_size: usize) -> Frame<'a> {
+        Frame {
+            variable_maps: vec![HashMap::new()],
+            scope_offsets: Vec::new(),
+            offset: header_size,
+            max_offset: header_size,
+        }
+    }
+
+    // returns the offset of the lowest address of the new variable
+    fn allocate(&mut self, name: &'a str, size: usize, align: usize) -> usize {
+        self.offset = (self.offset + size).div_ceil(align) * align;
+        self.max_offset = self.max_offset.max(self.offset);
+        if let Some(variable_map) = self.variable_maps.last_mut() {
+            if variable_map.contains_key(name) {
+                panic!("Redefinition of {}", name);
+            }
+            variable_map.insert(name, Some(self.offset));
+        }
+        self.offset
+    }
+
+    // lets the name refer to the global variable in the current scope,
+    // hiding any local variable of the same name in the enclosing scopes
+    fn alias_global(&mut self, name: &'a str) {
+        if let Some(variable_map) = self.variable_maps.last_mut() {
+            variable_map.insert(name, None);
+        }
+    }
+
+    fn find(&self, name: &str) -> Option<usize> {
+        self.variable_maps
+            .iter()
+            .rev()
+            .find_map(|variable_map| variable_map.get(name).copied())
+            .flatten()
+    }
+
+    fn begin_scope(&mut self) {
+        self.variable_maps.push(HashMap::new());
+        self.scope_offsets.push(self.offset);
+    }
+
+    fn end_scope(&mut self) {
+        self.variable_maps.pop();
+        if let Some(offset) = self.scope_offsets.pop() {
+            self.offset = offset;
+        }
+    }
+}
+
+pub fn generate_native_code(mid_commands: &[String]) -> Vec<String> {
     let mut native_commands = Vec::new();
-    native_commands.push(String::from(".global main"));
-    native_commands.push(String::from("main:"));
-    native_commands.push(format!("\tmv s0, sp"));
 
-    let mut variable_map = HashMap::new();
+    let mut frame = Frame::new(16);
+    let mut frame_idx = 0;
+    let mut function_name = "";
+    let mut header_size = 16;
+    let mut globals: HashSet<&str> = HashSet::new();
+    // the static functions and variables, which are not exported by .global
+    let mut local_symbols: HashSet<&str> = HashSet::new();
+    // the numbers of the integer registers, the floating-point registers and the stack slots
+    // taken by the parameters of the current function
+    let mut param_counts = (0, 0, 0);
+
+    // the number of 8-byte values on the operand stack, which is tracked to keep sp
+    // 16-byte aligned at call sites (the frame itself is a multiple of 16 bytes)
+    let mut depth: usize = 0;
+    // the depth at each label, which is the depth at any jump to it
+    // (e.g. both branches of `&&` reach the end with the result on the stack)
+    let mut label_depths: HashMap<&str, usize> = HashMap::new();
+
+    // the address of a function may be taken before the function is defined,
+    // and a global variable may be used before its definition follows a tentative one
+    for command in mid_commands.iter() {
+        let v: Vec<&str> = command[..].split(' ').collect();
+        if let "FUNCTION" | "GLOBAL" | "DATA" | "RODATA" = v[0] {
+            globals.insert(v[1]);
+        }
+    }
 
     for mid_command in mid_commands.iter() {
         println!("// {}", mid_command);
@@ -16,106 +106,738 @@ pub fn generate_native_code(mid_commands: &Vec<String>) -> Vec<String> {
     for command in mid_commands.iter() {
         let v: Vec<&str> = command[..].split(' ').collect();
         match v[0] {
-            "PUSH" => {
-                // push v[1]
-                native_commands.push(format!("\tadd t0, x0, {}", v[1]));
-                native_commands.push(format!("\tsd t0, -8(sp)"));
-                native_commands.push(format!("\taddi sp, sp, -8"));
+            "FUNCTION" => {
+                // `FUNCTION name ...` begins a variadic function
+                function_name = v[1];
+                let is_variadic = v.get(2) == Some(&"...");
+                header_size = if is_variadic { 80 } else { 16 };
+                frame = Frame::new(header_size);
+                depth = 0;
+                param_counts = (0, 0, 0);
+                native_commands.push(String::from(".text"));
+                if !local_symbols.contains(function_name) {
+                    native_commands.push(format!(".global {}", function_name));
+                }
+                native_commands.push(format!("{}:", function_name));
+
+                // save ra and s0, then let s0 point at the top of the new frame
+                native_commands.push(format!("\taddi sp, sp, -{}", header_size));
+                native_commands.push(String::from("\tsd ra, 8(sp)"));
+                native_commands.push(String::from("\tsd s0, 0(sp)"));
+                native_commands.push(format!("\taddi s0, sp, {}", header_size));
+
+                // spill a0-a7 so that all the arguments lie contiguously in memory,
+                // where va_arg walks through them
+                if is_variadic {
+                    for i in 0..8 {
+                        native_commands.push(format!("\tsd a{}, -{}(s0)", i, 64 - 8 * i));
+                    }
+                }
+
+                // the size of the local variables is known only at the end of the function,
+                // so it is patched in later
+                frame_idx = native_commands.len();
+                native_commands.push(String::new());
             }
-            "STORE" => {
-                // pop t0
-                native_commands.push(format!("\tld t0, 0(sp)"));
-                native_commands.push(format!("\taddi sp, sp, 8"));
+            "FUNCTIONEND" => {
+                native_commands.push(format!(".L.return.{}:", function_name));
+                native_commands.push(format!("\taddi sp, s0, -{}", header_size));
+                native_commands.push(String::from("\tld ra, 8(sp)"));
+                native_commands.push(String::from("\tld s0, 0(sp)"));
+                native_commands.push(format!("\taddi sp, sp, {}", header_size));
+                native_commands.push(String::from("\tret"));
 
-                match variable_map.get(v[1]) {
-                    Some(offset) => {
-                        // store t0 to s0-offset
-                        native_commands.push(format!("\tsd t0, -{}(s0)", offset));
+                // keep sp 16-byte aligned as required by the ABI
+                let locals_size = frame.max_offset.div_ceil(16) * 16 - header_size;
+                native_commands[frame_idx] = format!("\tli t0, {}\n\tsub sp, sp, t0", locals_size);
+            }
+            "GLOBAL" => {
+                // a variable without an initializer goes to .bss, which is zero-filled
+                globals.insert(v[1]);
+                native_commands.push(String::from(".bss"));
+                if !local_symbols.contains(v[1]) {
+                    native_commands.push(format!(".global {}", v[1]));
+                }
+                native_commands.push(format!(".balign {}", v[3]));
+                native_commands.push(format!("{}:", v[1]));
+                native_commands.push(format!("\t.zero {}", v[2]));
+            }
+            "DATA" | "RODATA" => {
+                // an initialized variable goes to .data (or .rodata if it is read-only),
+                // followed by its VALUE and ZERO commands
+                globals.insert(v[1]);
+                if v[0] == "DATA" {
+                    native_commands.push(String::from(".data"));
+                } else {
+                    native_commands.push(String::from(".section .rodata"));
+                }
+                if !local_symbols.contains(v[1]) {
+                    native_commands.push(format!(".global {}", v[1]));
+                }
+                native_commands.push(format!(".balign {}", v[2]));
+                native_commands.push(format!("{}:", v[1]));
+            }
+            "LOCAL" => {
+                local_symbols.insert(v[1]);
+            }
+            "VALUE" => {
+                let size: usize = v[1].parse().unwrap();
+                native_commands.push(format!("\t{} {}", data_directive(size), v[2]));
+            }
+            "ZERO" => {
+                native_commands.push(format!("\t.zero {}", v[1]));
+            }
+            "EXTERN" => {
+                // an extern variable or function is defined elsewhere, and the linker resolves its address
+                globals.insert(v[1]);
+                frame.alias_global(v[1]);
+            }
+            "FLOAT" => {
+                // a floating-point constant is read-only as well, and it is kept as its bit pattern
+                let size: usize = v[2].parse().unwrap();
+                globals.insert(v[1]);
+                native_commands.push(String::from(".section .rodata"));
+                native_commands.push(format!(".balign {}", size));
+                native_commands.push(format!("{}:", v[1]));
+                native_commands.push(format!("\t{} {}", data_directive(size), v[3]));
+            }
+            "STRING" => {
+                // the bytes of a string literal are read-only
+                globals.insert(v[1]);
+                native_commands.push(String::from(".section .rodata"));
+                native_commands.push(format!("{}:", v[1]));
+                native_commands.push(format!("\t.byte {}", v[2..].join(", ")));
+            }
+            "PARAM" => {
+                // v[3] is the format of the parameter (see CALL)
+                let size: usize = v[2].parse().unwrap();
+                let offset = frame.allocate(v[1], size, size);
+                let (int_count, float_count, stack_count) = &mut param_counts;
+                if v[3] != "l" && *float_count < 8 {
+                    native_commands.push(format!(
+                        "\t{} fa{}, -{}(s0)",
+                        float_store_instruction(v[3]),
+                        float_count,
+                        offset
+                    ));
+                    *float_count += 1;
+                } else if *int_count < 8 {
+                    native_commands.push(format!(
+                        "\t{} a{}, -{}(s0)",
+                        store_instruction(size),
+                        int_count,
+                        offset
+                    ));
+                    *int_count += 1;
+                } else {
+                    // the other arguments are passed on the caller's stack,
+                    // which starts at the top of this frame
+                    native_commands.push(format!("\tld t0, {}(s0)", 8 * *stack_count));
+                    native_commands.push(format!(
+                        "\t{} t0, -{}(s0)",
+                        store_instruction(size),
+                        offset
+                    ));
+                    *stack_count += 1;
+                }
+            }
+            "VASTART" => {
+                // push the address of the first variadic argument, which follows the named
+                // parameters in the register save area or on the caller's stack
+                let (int_count, _, stack_count) = param_counts;
+                native_commands.push(format!(
+                    "\taddi t0, s0, {}",
+                    8 * (int_count + stack_count) as i64 - 64
+                ));
+                native_commands.push(String::from("\tsd t0, -8(sp)"));
+                native_commands.push(String::from("\taddi sp, sp, -8"));
+                depth += 1;
+            }
+            "CALL" | "CALLPOINTER" => {
+                // `CALL name n format formats...` calls a function by its name, while
+                // `CALLPOINTER n format formats...` calls the function whose address is
+                // on the top of the stack, which is popped to t1 first
+                let (callee, operands) = if v[0] == "CALL" {
+                    (Some(v[1]), &v[2..])
+                } else {
+                    native_commands.push(String::from("\tld t1, 0(sp)"));
+                    native_commands.push(String::from("\taddi sp, sp, 8"));
+                    depth -= 1;
+                    (None, &v[1..])
+                };
+
+                // operands[1] is the format of the return value and operands[2..] those of
+                // the arguments: a float (s) or double (d) is passed in fa0-fa7 while they last,
+                // the others (l, or w for an unsigned int, which is sign-extended) in a0-a7,
+                // and the rest stay on the stack in their order
+                let arg_count: usize = operands[0].parse().unwrap();
+                let mut int_count = 0;
+                let mut float_count = 0;
+                let mut stack_args = Vec::new();
+                for (i, format) in operands[2..2 + arg_count].iter().enumerate() {
+                    if matches!(*format, "s" | "d") && float_count < 8 {
+                        native_commands.push(format!(
+                            "\t{} fa{}, {}(sp)",
+                            float_load_instruction(format),
+                            float_count,
+                            8 * i
+                        ));
+                        float_count += 1;
+                    } else if int_count < 8 {
+                        native_commands.push(format!("\tld a{}, {}(sp)", int_count, 8 * i));
+                        if *format == "w" {
+                            native_commands
+                                .push(format!("\tsext.w a{}, a{}", int_count, int_count));
+                        }
+                        int_count += 1;
+                    } else {
+                        // lw sign-extends the value in its stack slot
+                        if *format == "w" {
+                            native_commands.push(format!("\tlw t0, {}(sp)", 8 * i));
+                            native_commands.push(format!("\tsd t0, {}(sp)", 8 * i));
+                        }
+                        stack_args.push(i);
+                    }
+                }
+
+                // the stack-passed arguments are packed right above the register ones,
+                // starting from the last one as they only move up
+                let reg_arg_count = int_count + float_count;
+                for (j, i) in stack_args.iter().enumerate().rev() {
+                    if reg_arg_count + j != *i {
+                        native_commands.push(format!("\tld t0, {}(sp)", 8 * i));
+                        native_commands.push(format!("\tsd t0, {}(sp)", 8 * (reg_arg_count + j)));
+                    }
+                }
+                if reg_arg_count > 0 {
+                    native_commands.push(format!("\taddi sp, sp, {}", 8 * reg_arg_count));
+                }
+                depth -= reg_arg_count;
+
+                // if sp is not 16-byte aligned, pad the stack below the stack-passed arguments
+                // by moving them down 8 bytes
+                let stack_arg_count = stack_args.len();
+                let padding = depth % 2;
+                if padding == 1 {
+                    native_commands.push(String::from("\taddi sp, sp, -8"));
+                    for i in 0..stack_arg_count {
+                        native_commands.push(format!("\tld t0, {}(sp)", 8 * (i + 1)));
+                        native_commands.push(format!("\tsd t0, {}(sp)", 8 * i));
+                    }
+                }
+                match callee {
+                    Some(name) => {
+                        native_commands.push(format!("\tcall {}", name));
                     }
                     _ => {
-                        // if the v[1] is new variable, create a new entry in variable_map and move sp
-                        // (assume that no operand is in the stack when storing)
-                        let new_offset = 8 * (variable_map.len() + 1);
-                        variable_map.insert(v[1], new_offset);
-                        native_commands.push(format!("\tsd t0, -{}(s0)", new_offset));
-                        native_commands.push(format!("\taddi sp, sp, -8"));
+                        native_commands.push(String::from("\tjalr t1"));
                     }
                 }
+                if stack_arg_count + padding > 0 {
+                    native_commands.push(format!(
+                        "\taddi sp, sp, {}",
+                        8 * (stack_arg_count + padding)
+                    ));
+                }
+                depth -= stack_arg_count;
+
+                // push a0 (or fa0), where an unsigned int is zero-extended again by the next command
+                if matches!(operands[1], "l" | "w") {
+                    native_commands.push(String::from("\tsd a0, -8(sp)"));
+                } else {
+                    native_commands.push(format!(
+                        "\t{} fa0, -8(sp)",
+                        float_store_instruction(operands[1])
+                    ));
+                }
+                native_commands.push(String::from("\taddi sp, sp, -8"));
+                depth += 1;
+            }
+            "PUSH" => {
+                // push v[1] (li accepts any 64-bit constant)
+                native_commands.push(format!("\tli t0, {}", v[1]));
+                native_commands.push(String::from("\tsd t0, -8(sp)"));
+                native_commands.push(String::from("\taddi sp, sp, -8"));
+                depth += 1;
+            }
+            "STORE" => {
+                // peek t0 (the assigned value stays on the stack as the value of the assignment)
+                native_commands.push(String::from("\tld t0, 0(sp)"));
+
+                let size: usize = v[2].parse().unwrap();
+                if frame.find(v[1]).is_none() && globals.contains(v[1]) {
+                    // store t0 to the global variable
+                    native_commands.push(format!("\tla t1, {}", v[1]));
+                    native_commands.push(format!("\t{} t0, 0(t1)", store_instruction(size)));
+                } else {
+                    // if the v[1] is new variable, declare it implicitly in the current scope
+                    let offset = match frame.find(v[1]) {
+                        Some(offset) => offset,
+                        _ => frame.allocate(v[1], size, size),
+                    };
+
+                    // store t0 to s0-offset
+                    native_commands.push(format!(
+                        "\t{} t0, -{}(s0)",
+                        store_instruction(size),
+                        offset
+                    ));
+                }
+            }
+            "DECLARE" => {
+                frame.allocate(v[1], v[2].parse().unwrap(), v[3].parse().unwrap());
             }
-            "LOAD" => {
-                let offset = variable_map.get(v[1]);
+            "SCOPEBEGIN" => {
+                frame.begin_scope();
+            }
+            "SCOPEEND" => {
+                frame.end_scope();
+            }
+            "LOAD" | "LOADU" => {
+                let offset = frame.find(v[1]);
                 match offset {
                     Some(offset_) => {
                         // load s0-offset to t0
-                        native_commands.push(format!("\tld t0, -{}(s0)", offset_));
+                        let size: usize = v[2].parse().unwrap();
+                        native_commands.push(format!(
+                            "\t{} t0, -{}(s0)",
+                            load_instruction(size, v[0] == "LOADU"),
+                            offset_
+                        ));
+
+                        // push t0
+                        native_commands.push(String::from("\tsd t0, -8(sp)"));
+                        native_commands.push(String::from("\taddi sp, sp, -8"));
+                        depth += 1;
+                    }
+                    _ if globals.contains(v[1]) => {
+                        // load the global variable to t0
+                        let size: usize = v[2].parse().unwrap();
+                        native_commands.push(format!("\tla t0, {}", v[1]));
+                        native_commands.push(format!(
+                            "\t{} t0, 0(t0)",
+                            load_instruction(size, v[0] == "LOADU")
+                        ));
 
                         // push t0
-                        native_commands.push(format!("\tsd t0, -8(sp)"));
-                        native_commands.push(format!("\taddi sp, sp, -8"));
+                        native_commands.push(String::from("\tsd t0, -8(sp)"));
+                        native_commands.push(String::from("\taddi sp, sp, -8"));
+                        depth += 1;
                     }
                     _ => {
                         panic!("Cannot load from {}", v[1]);
                     }
                 }
             }
-            "ADD" | "SUB" | "MUL" | "DIV" | "EQUAL" | "NONEQUAL" | "LT" | "ELT" => {
+            "ADDRESS" => match frame.find(v[1]) {
+                Some(offset) => {
+                    // push s0-offset
+                    native_commands.push(format!("\taddi t0, s0, -{}", offset));
+                    native_commands.push(String::from("\tsd t0, -8(sp)"));
+                    native_commands.push(String::from("\taddi sp, sp, -8"));
+                    depth += 1;
+                }
+                _ if globals.contains(v[1]) => {
+                    native_commands.push(format!("\tla t0, {}", v[1]));
+                    native_commands.push(String::from("\tsd t0, -8(sp)"));
+                    native_commands.push(String::from("\taddi sp, sp, -8"));
+                    depth += 1;
+                }
+                _ => {
+                    panic!("Cannot take the address of {}", v[1]);
+                }
+            },
+            "DEREF" | "DEREFU" => {
+                // replace the address on the top of the stack with the value it points to
+                let size: usize = v[1].parse().unwrap();
+                native_commands.push(String::from("\tld t0, 0(sp)"));
+                native_commands.push(format!(
+                    "\t{} t0, 0(t0)",
+                    load_instruction(size, v[0] == "DEREFU")
+                ));
+                native_commands.push(String::from("\tsd t0, 0(sp)"));
+            }
+            "ASSIGN" => {
+                // pop the value t0 and the address t1, then push the value back
+                // as the value of the assignment
+                let size: usize = v[1].parse().unwrap();
+                native_commands.push(String::from("\tld t0, 0(sp)"));
+                native_commands.push(String::from("\tld t1, 8(sp)"));
+                native_commands.push(format!("\t{} t0, 0(t1)", store_instruction(size)));
+                native_commands.push(String::from("\taddi sp, sp, 8"));
+                native_commands.push(String::from("\tsd t0, 0(sp)"));
+                depth -= 1;
+            }
+            "MEMBER" => {
+                // add the offset of a member to the address of the struct on the top of the stack
+                native_commands.push(String::from("\tld t0, 0(sp)"));
+                native_commands.push(format!("\taddi t0, t0, {}", v[1]));
+                native_commands.push(String::from("\tsd t0, 0(sp)"));
+            }
+            "COPY" => {
+                // pop the source address t0 and the destination address t1,
+                // copy v[1] bytes, then push the destination back
+                let size: usize = v[1].parse().unwrap();
+                native_commands.push(String::from("\tld t0, 0(sp)"));
+                native_commands.push(String::from("\tld t1, 8(sp)"));
+                for i in 0..size {
+                    native_commands.push(format!("\tlb t2, {}(t0)", i));
+                    native_commands.push(format!("\tsb t2, {}(t1)", i));
+                }
+                native_commands.push(String::from("\taddi sp, sp, 8"));
+                depth -= 1;
+            }
+            "ADD" | "SUB" | "MUL" | "DIV" | "DIVU" | "MOD" | "MODU" | "AND" | "OR" | "XOR"
+            | "SHL" | "SHR" | "SHRU" | "EQUAL" | "NONEQUAL" | "LT" | "LTU" | "ELT" | "ELTU" => {
                 // pop t0
-                native_commands.push(format!("\tld t0, 0(sp)"));
-                native_commands.push(format!("\taddi sp, sp, 8"));
+                native_commands.push(String::from("\tld t0, 0(sp)"));
+                native_commands.push(String::from("\taddi sp, sp, 8"));
 
                 // pop t1
-                native_commands.push(format!("\tld t1, 0(sp)"));
-                native_commands.push(format!("\taddi sp, sp, 8"));
+                native_commands.push(String::from("\tld t1, 0(sp)"));
+                native_commands.push(String::from("\taddi sp, sp, 8"));
 
                 // t0 = t1 + t0
+                // (int operations use the 32-bit variants, which sign-extend their results)
+                let suffix = if v.get(1) == Some(&"4") { "w" } else { "" };
                 match v[0] {
                     "ADD" => {
-                        native_commands.push(format!("\tadd t0, t1, t0"));
+                        native_commands.push(format!("\tadd{} t0, t1, t0", suffix));
                     }
                     "SUB" => {
-                        native_commands.push(format!("\tsub t0, t1, t0"));
+                        native_commands.push(format!("\tsub{} t0, t1, t0", suffix));
                     }
                     "MUL" => {
-                        native_commands.push(format!("\tmul t0, t1, t0"));
+                        native_commands.push(format!("\tmul{} t0, t1, t0", suffix));
                     }
                     "DIV" => {
-                        native_commands.push(format!("\tdiv t0, t1, t0"));
+                        native_commands.push(format!("\tdiv{} t0, t1, t0", suffix));
+                    }
+                    "DIVU" => {
+                        native_commands.push(format!("\tdivu{} t0, t1, t0", suffix));
+                    }
+                    "MOD" => {
+                        native_commands.push(format!("\trem{} t0, t1, t0", suffix));
+                    }
+                    "MODU" => {
+                        native_commands.push(format!("\tremu{} t0, t1, t0", suffix));
+                    }
+                    "AND" => {
+                        native_commands.push(String::from("\tand t0, t1, t0"));
+                    }
+                    "OR" => {
+                        native_commands.push(String::from("\tor t0, t1, t0"));
+                    }
+                    "XOR" => {
+                        native_commands.push(String::from("\txor t0, t1, t0"));
+                    }
+                    "SHL" => {
+                        native_commands.push(format!("\tsll{} t0, t1, t0", suffix));
+                    }
+                    "SHR" => {
+                        native_commands.push(format!("\tsra{} t0, t1, t0", suffix));
+                    }
+                    "SHRU" => {
+                        native_commands.push(format!("\tsrl{} t0, t1, t0", suffix));
                     }
                     "EQUAL" => {
-                        native_commands.push(format!("\tsub t0, t0, t1"));
-                        native_commands.push(format!("\tseqz t0, t0"));
+                        native_commands.push(String::from("\tsub t0, t0, t1"));
+                        native_commands.push(String::from("\tseqz t0, t0"));
                     }
                     "NONEQUAL" => {
-                        native_commands.push(format!("\tsub t0, t1, t0"));
-                        native_commands.push(format!("\tsnez t0, t0"));
+                        native_commands.push(String::from("\tsub t0, t1, t0"));
+                        native_commands.push(String::from("\tsnez t0, t0"));
                     }
                     "LT" => {
-                        native_commands.push(format!("\tslt t0, t1, t0"));
+                        native_commands.push(String::from("\tslt t0, t1, t0"));
+                    }
+                    "LTU" => {
+                        native_commands.push(String::from("\tsltu t0, t1, t0"));
                     }
                     "ELT" => {
-                        native_commands.push(format!("\tsgt t0, t1, t0"));
-                        native_commands.push(format!("\txori t0, t0, 1"));
+                        native_commands.push(String::from("\tsgt t0, t1, t0"));
+                        native_commands.push(String::from("\txori t0, t0, 1"));
+                    }
+                    "ELTU" => {
+                        native_commands.push(String::from("\tsgtu t0, t1, t0"));
+                        native_commands.push(String::from("\txori t0, t0, 1"));
                     }
                     _ => {}
                 }
 
                 // push t0
-                native_commands.push(format!("\tsd t0, -8(sp)"));
-                native_commands.push(format!("\taddi sp, sp, -8"));
+                native_commands.push(String::from("\tsd t0, -8(sp)"));
+                native_commands.push(String::from("\taddi sp, sp, -8"));
+                depth -= 1;
+            }
+            "FADD" | "FSUB" | "FMUL" | "FDIV" | "FEQUAL" | "FNONEQUAL" | "FLT" | "FELT" => {
+                // pop ft0 and ft1 in the format v[1]
+                let load = float_load_instruction(v[1]);
+                native_commands.push(format!("\t{} ft0, 0(sp)", load));
+                native_commands.push(format!("\t{} ft1, 8(sp)", load));
+                native_commands.push(String::from("\taddi sp, sp, 16"));
+
+                // ft0 = ft1 + ft0, or t0 = ft1 == ft0 for the comparisons
+                let format = v[1];
+                match v[0] {
+                    "FADD" => {
+                        native_commands.push(format!("\tfadd.{} ft0, ft1, ft0", format));
+                    }
+                    "FSUB" => {
+                        native_commands.push(format!("\tfsub.{} ft0, ft1, ft0", format));
+                    }
+                    "FMUL" => {
+                        native_commands.push(format!("\tfmul.{} ft0, ft1, ft0", format));
+                    }
+                    "FDIV" => {
+                        native_commands.push(format!("\tfdiv.{} ft0, ft1, ft0", format));
+                    }
+                    "FEQUAL" => {
+                        native_commands.push(format!("\tfeq.{} t0, ft1, ft0", format));
+                    }
+                    "FNONEQUAL" => {
+                        native_commands.push(format!("\tfeq.{} t0, ft1, ft0", format));
+                        native_commands.push(String::from("\txori t0, t0, 1"));
+                    }
+                    "FLT" => {
+                        native_commands.push(format!("\tflt.{} t0, ft1, ft0", format));
+                    }
+                    "FELT" => {
+                        native_commands.push(format!("\tfle.{} t0, ft1, ft0", format));
+                    }
+                    _ => {}
+                }
+
+                // push ft0 (or t0)
+                if matches!(v[0], "FADD" | "FSUB" | "FMUL" | "FDIV") {
+                    native_commands
+                        .push(format!("\t{} ft0, -8(sp)", float_store_instruction(format)));
+                } else {
+                    native_commands.push(String::from("\tsd t0, -8(sp)"));
+                }
+                native_commands.push(String::from("\taddi sp, sp, -8"));
+                depth -= 1;
+            }
+            "FCAST" => {
+                // convert the top of the stack from the format v[2] to v[1],
+                // where an integer is l (or lu if unsigned) and is truncated toward zero
+                let is_floating = |format: &str| format == "s" || format == "d";
+                match (is_floating(v[1]), is_floating(v[2])) {
+                    (true, true) => {
+                        native_commands
+                            .push(format!("\t{} ft0, 0(sp)", float_load_instruction(v[2])));
+                        native_commands.push(format!("\tfcvt.{}.{} ft0, ft0", v[1], v[2]));
+                        native_commands
+                            .push(format!("\t{} ft0, 0(sp)", float_store_instruction(v[1])));
+                    }
+                    (true, false) => {
+                        native_commands.push(String::from("\tld t0, 0(sp)"));
+                        native_commands.push(format!("\tfcvt.{}.{} ft0, t0", v[1], v[2]));
+                        native_commands
+                            .push(format!("\t{} ft0, 0(sp)", float_store_instruction(v[1])));
+                    }
+                    _ => {
+                        native_commands
+                            .push(format!("\t{} ft0, 0(sp)", float_load_instruction(v[2])));
+                        native_commands.push(format!("\tfcvt.{}.{} t0, ft0, rtz", v[1], v[2]));
+                        native_commands.push(String::from("\tsd t0, 0(sp)"));
+                    }
+                }
+            }
+            "NOT" => {
+                native_commands.push(String::from("\tld t0, 0(sp)"));
+                native_commands.push(String::from("\tnot t0, t0"));
+                native_commands.push(String::from("\tsd t0, 0(sp)"));
+            }
+            "NEG" => {
+                let suffix = if v[1] == "4" { "w" } else { "" };
+                native_commands.push(String::from("\tld t0, 0(sp)"));
+                native_commands.push(format!("\tneg{} t0, t0", suffix));
+                native_commands.push(String::from("\tsd t0, 0(sp)"));
+            }
+            "FNEG" => {
+                let format = v[1];
+                native_commands.push(format!("\t{} ft0, 0(sp)", float_load_instruction(format)));
+                native_commands.push(format!("\tfneg.{} ft0, ft0", format));
+                native_commands.push(format!("\t{} ft0, 0(sp)", float_store_instruction(format)));
+            }
+            "CAST" => {
+                // truncate the top of the stack to v[1] bytes and sign-extend it back to 64 bits
+                native_commands.push(String::from("\tld t0, 0(sp)"));
+                match v[1] {
+                    "4" => {
+                        native_commands.push(String::from("\tsext.w t0, t0"));
+                    }
+                    _ => {
+                        let shift = 64 - 8 * v[1].parse::<usize>().unwrap();
+                        native_commands.push(format!("\tslli t0, t0, {}", shift));
+                        native_commands.push(format!("\tsrai t0, t0, {}", shift));
+                    }
+                }
+                native_commands.push(String::from("\tsd t0, 0(sp)"));
+            }
+            "CASTU" => {
+                // truncate the top of the stack to v[1] bytes and zero-extend it back to 64 bits
+                native_commands.push(String::from("\tld t0, 0(sp)"));
+                match v[1] {
+                    "1" => {
+                        native_commands.push(String::from("\tandi t0, t0, 255"));
+                    }
+                    _ => {
+                        let shift = 64 - 8 * v[1].parse::<usize>().unwrap();
+                        native_commands.push(format!("\tslli t0, t0, {}", shift));
+                        native_commands.push(format!("\tsrli t0, t0, {}", shift));
+                    }
+                }
+                native_commands.push(String::from("\tsd t0, 0(sp)"));
+            }
+            "POP" => {
+                // pop a0 (the value of the last expression statement is returned
+                // when the end of the function is reached)
+                native_commands.push(String::from("\tld a0, 0(sp)"));
+                native_commands.push(String::from("\taddi sp, sp, 8"));
+                depth -= 1;
+            }
+            "LABEL" => {
+                native_commands.push(format!("{}:", v[1]));
+                if let Some(label_depth) = label_depths.get(v[1]) {
+                    depth = *label_depth;
+                }
+            }
+            "JUMP" => {
+                native_commands.push(format!("\tj {}", v[1]));
+                label_depths.insert(v[1], depth);
+            }
+            "JUMPZERO" | "JUMPNONZERO" => {
+                // pop t0 and branch if it is zero (or nonzero)
+                native_commands.push(String::from("\tld t0, 0(sp)"));
+                native_commands.push(String::from("\taddi sp, sp, 8"));
+                if v[0] == "JUMPZERO" {
+                    native_commands.push(format!("\tbeqz t0, {}", v[1]));
+                } else {
+                    native_commands.push(format!("\tbnez t0, {}", v[1]));
+                }
+                depth -= 1;
+                label_depths.insert(v[1], depth);
+            }
+            "SWITCH" => {
+                // pop t0 and jump to the case label of its value
+                native_commands.push(String::from("\tld t0, 0(sp)"));
+                native_commands.push(String::from("\taddi sp, sp, 8"));
+                depth -= 1;
+                let default_label = v[2];
+                label_depths.insert(default_label, depth);
+                let cases: Vec<(i64, &str)> = v[3..]
+                    .chunks(2)
+                    .map(|case| (case[0].parse().unwrap(), case[1]))
+                    .collect();
+                for (_, label) in cases.iter() {
+                    label_depths.insert(label, depth);
+                }
+                let min = cases.iter().map(|case| case.0).min().unwrap_or(0);
+                let max = cases.iter().map(|case| case.0).max().unwrap_or(0);
+                let range = max as i128 - min as i128 + 1;
+                if cases.len() >= 4 && range <= 2 * cases.len() as i128 {
+                    // dense cases index a jump table, where the gaps go to the default label
+                    native_commands.push(format!("\tli t1, {}", min));
+                    native_commands.push(String::from("\tsub t0, t0, t1"));
+                    native_commands.push(format!("\tli t1, {}", range));
+                    native_commands.push(format!("\tbgeu t0, t1, {}", default_label));
+                    native_commands.push(format!("\tla t1, .L.table.{}", v[1]));
+                    native_commands.push(String::from("\tslli t0, t0, 3"));
+                    native_commands.push(String::from("\tadd t1, t1, t0"));
+                    native_commands.push(String::from("\tld t1, 0(t1)"));
+                    native_commands.push(String::from("\tjr t1"));
+                    native_commands.push(String::from(".section .rodata"));
+                    native_commands.push(String::from(".balign 8"));
+                    native_commands.push(format!(".L.table.{}:", v[1]));
+                    for value in min..=max {
+                        let label = match cases.iter().find(|case| case.0 == value) {
+                            Some((_, label)) => label,
+                            _ => default_label,
+                        };
+                        native_commands.push(format!("\t.dword {}", label));
+                    }
+                    native_commands.push(String::from(".text"));
+                } else {
+                    // sparse cases are compared one by one
+                    for (value, label) in cases.iter() {
+                        native_commands.push(format!("\tli t1, {}", value));
+                        native_commands.push(format!("\tbeq t0, t1, {}", label));
+                    }
+                    native_commands.push(format!("\tj {}", default_label));
+                }
             }
             "RETURN" => {
-                native_commands.push(format!("\tld a0, 0(sp)"));
-                native_commands.push(format!("\taddi sp, sp, 8"));
-                native_commands.push(format!("\tret"));
+                // pop a0 (or fa0 for the format s or d) unless no value is returned,
+                // sign-extending an unsigned int (w)
+                match v.get(1) {
+                    Some(&"l") => {
+                        native_commands.push(String::from("\tld a0, 0(sp)"));
+                    }
+                    Some(&"w") => {
+                        native_commands.push(String::from("\tld a0, 0(sp)"));
+                        native_commands.push(String::from("\tsext.w a0, a0"));
+                    }
+                    Some(format) => {
+                        native_commands
+                            .push(format!("\t{} fa0, 0(sp)", float_load_instruction(format)));
+                    }
+                    _ => {}
+                }
+                if v.len() > 1 {
+                    native_commands.push(String::from("\taddi sp, sp, 8"));
+                    depth -= 1;
+                }
+                native_commands.push(format!("\tj .L.return.{}", function_name));
             }
             _ => {}
         }
     }
 
-    // pop a0
-    native_commands.push(format!("\tld a0, 0(sp)"));
-    native_commands.push(format!("\taddi sp, sp, 8"));
-    native_commands.push(format!("\tret"));
-
     native_commands
 }
+
+fn load_instruction(size: usize, unsigned: bool) -> &'static str {
+    match (size, unsigned) {
+        (1, false) => "lb",
+        (1, true) => "lbu",
+        (2, false) => "lh",
+        (2, true) => "lhu",
+        (4, false) => "lw",
+        (4, true) => "lwu",
+        _ => "ld",
+    }
+}
+
+fn float_load_instruction(format: &str) -> &'static str {
+    match format {
+        "s" => "flw",
+        _ => "fld",
+    }
+}
+
+fn float_store_instruction(format: &str) -> &'static str {
+    match format {
+        "s" => "fsw",
+        _ => "fsd",
+    }
+}
+
+fn store_instruction(size: usize) -> &'static str {
+    match size {
+        1 => "sb",
+        2 => "sh",
+        4 => "sw",
+        _ => "sd",
+    }
+}
+
+fn data_directive(size: usize) -> &'static str {
+    match size {
+        1 => ".byte",
+        2 => ".half",
+        4 => ".word",
+        _ => ".dword",
+    }
+}
@@ -1,41 +1,102 @@
 use crate::compiler::*;
+use std::collections::HashMap;
 
-pub fn parsing(tokens: &Vec<Token>) -> Vec<Option<Node>> {
+// A typedef name is an identifier, but the parser has to know that it names a type to tell
+// a declaration from an expression. The ordinary identifiers are therefore recorded in one map
+// per scope (innermost last) with whether each is a typedef name, so that a variable,
+// a function or an enumerator of the same name hides a typedef name of an outer scope.
+struct ParseContext {
+    scopes: Vec<HashMap<String, bool>>,
+}
+
+pub fn parsing(tokens: &[Token]) -> Vec<Option<Node>> {
     let mut idx: usize = 0;
-    let program = program(&tokens, &mut idx);
-    program
+    // va_list is predefined (see the type checker)
+    let mut context = ParseContext {
+        scopes: vec![HashMap::from([(String::from("va_list"), true)])],
+    };
+
+    program(tokens, &mut idx, &mut context)
+}
+
+fn enter_scope(context: &mut ParseContext) {
+    context.scopes.push(HashMap::new());
+}
+
+fn leave_scope(context: &mut ParseContext) {
+    context.scopes.pop();
+}
+
+fn declare_name(context: &mut ParseContext, name: &str, is_typedef: bool) {
+    if let Some(scope) = context.scopes.last_mut() {
+        if scope
+            .get(name)
+            .is_some_and(|is_typedef_| *is_typedef_ != is_typedef)
+        {
+            panic!("Redefinition of {} as a different kind of symbol", name);
+        }
+        scope.insert(String::from(name), is_typedef);
+    }
+}
+
+fn is_typedef_name(tokens: &[Token], idx: usize, context: &ParseContext) -> bool {
+    match tokens.get(idx) {
+        Some(Token {
+            kind: TokenKind::TkVariable,
+            val,
+        }) => context
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(val))
+            .is_some_and(|is_typedef| *is_typedef),
+        _ => false,
+    }
 }
 
 fn create_node(val: &str, kind: NodeKind, left: Option<Node>, right: Option<Node>) -> Option<Node> {
-    match (left, right) {
-        (Some(node_left), Some(node_right)) => Some(Node {
-            val: String::from(val),
-            kind: kind,
-            left: Some(Box::new(node_left)),
-            right: Some(Box::new(node_right)),
-        }),
-        (Some(node_left), None) => Some(Node {
-            val: String::from(val),
-            kind: kind,
-            left: Some(Box::new(node_left)),
-            right: None,
-        }),
-        (None, Some(node_right)) => Some(Node {
-            val: String::from(val),
-            kind: kind,
-            left: None,
-            right: Some(Box::new(node_right)),
-        }),
-        (None, None) => Some(Node {
-            val: String::from(val),
-            kind: kind,
-            left: None,
-            right: None,
-        }),
-    }
-}
-
-fn consume(tokens: &Vec<Token>, idx: &mut usize, target_val: &str) -> bool {
+    let mut node = Node::new(val, kind);
+    node.left = left.map(Box::new);
+    node.right = right.map(Box::new);
+    Some(node)
+}
+
+fn create_if_node(cond: Option<Node>, then: Option<Node>, els: Option<Node>) -> Option<Node> {
+    let mut node = create_node("if", NodeKind::NdIf, None, None)?;
+    node.cond = cond.map(Box::new);
+    node.then = then.map(Box::new);
+    node.els = els.map(Box::new);
+    Some(node)
+}
+
+fn create_while_node(cond: Option<Node>, then: Option<Node>) -> Option<Node> {
+    let mut node = create_node("while", NodeKind::NdWhile, None, None)?;
+    node.cond = cond.map(Box::new);
+    node.then = then.map(Box::new);
+    Some(node)
+}
+
+fn create_for_node(
+    init: Option<Node>,
+    cond: Option<Node>,
+    inc: Option<Node>,
+    then: Option<Node>,
+) -> Option<Node> {
+    let mut node = create_node("for", NodeKind::NdFor, None, None)?;
+    node.init = init.map(Box::new);
+    node.cond = cond.map(Box::new);
+    node.inc = inc.map(Box::new);
+    node.then = then.map(Box::new);
+    Some(node)
+}
+
+fn expect(tokens: &[Token], idx: &mut usize, target_val: &str) {
+    if !consume(tokens, idx, target_val) {
+        panic!("Expected {}", target_val);
+    }
+}
+
+fn consume(tokens: &[Token], idx: &mut usize, target_val: &str) -> bool {
     let idx_: usize = *idx;
 
     if tokens.len() <= idx_ {
@@ -50,106 +111,989 @@ fn consume(tokens: &Vec<Token>, idx: &mut usize, target_val: &str) -> bool {
     }
 }
 
-fn program(tokens: &Vec<Token>, idx: &mut usize) -> Vec<Option<Node>> {
+fn program(tokens: &[Token], idx: &mut usize, context: &mut ParseContext) -> Vec<Option<Node>> {
     let mut vec: Vec<Option<Node>> = Vec::new();
+    if is_translation_unit(tokens, *idx, context) {
+        while *idx < tokens.len() {
+            if is_function(tokens, *idx, context) {
+                vec.push(function_definition(tokens, idx, context));
+            } else {
+                // a declaration at the top level defines global variables
+                vec.push(declaration(tokens, idx, context));
+            }
+        }
+    } else {
+        // a bare list of statements is compiled as the body of main
+        let mut node = create_node("main", NodeKind::NdFunction, None, None);
+        if let Some(main) = &mut node {
+            main.ty = Some(Type::new(TypeKind::TyLong));
+        }
+        while let Some(node_) = stmt(tokens, idx, context) {
+            if let Some(main) = &mut node {
+                main.body.push(node_);
+            }
+        }
+        vec.push(node);
+    }
+    vec.push(None);
+    vec
+}
+
+// A program is a list of functions and global variables if a function appears at the top level;
+// otherwise it is a bare list of statements.
+fn is_translation_unit(tokens: &[Token], idx: usize, context: &ParseContext) -> bool {
+    let mut depth = 0;
+    let mut is_statement_start = true;
+    for (i, token) in tokens.iter().enumerate().skip(idx) {
+        if depth == 0 && is_statement_start && is_function(tokens, i, context) {
+            return true;
+        }
+        match &token.val[..] {
+            "{" => {
+                depth += 1;
+            }
+            "}" => {
+                depth -= 1;
+            }
+            _ => {}
+        }
+        is_statement_start = matches!(&token.val[..], ";" | "{" | "}");
+    }
+    false
+}
+
+// A function starts with a definition `name(...) {` (optionally preceded by a return type)
+// or a declaration `type name(...);`, where a storage class counts as a type.
+// A typedef name may not have been declared yet while looking ahead for the first function,
+// but an identifier followed by another one can only be a typedef name.
+fn is_function(tokens: &[Token], idx: usize, context: &ParseContext) -> bool {
+    let is_type = |i: usize| {
+        is_type_name(tokens, i, context)
+            || matches!(
+                tokens.get(i..i + 2),
+                Some([first, second]) if matches!(first.kind, TokenKind::TkVariable)
+                    && matches!(second.kind, TokenKind::TkVariable)
+            )
+    };
+    let mut idx_ = idx;
+    let has_type = is_type(idx_);
+    while is_type(idx_) || matches!(tokens.get(idx_), Some(token) if token.val == "*") {
+        if let Some(Token {
+            kind: TokenKind::TkStruct | TokenKind::TkUnion | TokenKind::TkEnum,
+            ..
+        }) = tokens.get(idx_)
+        {
+            // skip the tag, while a member list means that this is a struct definition
+            if let Some(Token {
+                kind: TokenKind::TkVariable,
+                ..
+            }) = tokens.get(idx_ + 1)
+            {
+                idx_ += 1;
+            }
+            if matches!(tokens.get(idx_ + 1), Some(token) if token.val == "{") {
+                return false;
+            }
+        }
+        idx_ += 1;
+    }
+    match tokens.get(idx_) {
+        Some(Token {
+            kind: TokenKind::TkVariable,
+            ..
+        }) => {}
+        _ => {
+            return false;
+        }
+    }
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(idx_ + 1) {
+        match &token.val[..] {
+            "(" => {
+                depth += 1;
+            }
+            ")" => {
+                depth -= 1;
+                if depth == 0 {
+                    return match tokens.get(i + 1) {
+                        Some(token) => token.val == "{" || (has_type && token.val == ";"),
+                        _ => false,
+                    };
+                }
+            }
+            _ => {
+                if depth == 0 {
+                    return false;
+                }
+            }
+        }
+    }
+    false
+}
+
+fn is_type_name(tokens: &[Token], idx: usize, context: &ParseContext) -> bool {
+    is_typedef_name(tokens, idx, context)
+        || matches!(
+            tokens.get(idx),
+            Some(Token {
+                kind: TokenKind::TkVoid
+                    | TokenKind::TkInt
+                    | TokenKind::TkChar
+                    | TokenKind::TkLong
+                    | TokenKind::TkFloat
+                    | TokenKind::TkDouble
+                    | TokenKind::TkStruct
+                    | TokenKind::TkUnion
+                    | TokenKind::TkEnum
+                    | TokenKind::TkTypedef
+                    | TokenKind::TkSigned
+                    | TokenKind::TkUnsigned
+                    | TokenKind::TkStatic
+                    | TokenKind::TkExtern
+                    | TokenKind::TkConst
+                    | TokenKind::TkVolatile,
+                ..
+            })
+        )
+}
+
+// storage_class = ("static" | "extern")?
+fn storage_class(tokens: &[Token], idx: &mut usize) -> StorageClass {
+    if consume(tokens, idx, "static") {
+        StorageClass::ScStatic
+    } else if consume(tokens, idx, "extern") {
+        StorageClass::ScExtern
+    } else {
+        StorageClass::ScNone
+    }
+}
+
+// qualifiers = ("const" | "volatile")*
+// returns whether const is among them. every access to an object is generated as it is written
+// and nothing is kept in registers across statements, so volatile needs nothing more
+fn qualifiers(tokens: &[Token], idx: &mut usize) -> bool {
+    let mut is_const = false;
     loop {
-        let node = stmt(&tokens, idx);
-        match node {
+        if consume(tokens, idx, "const") {
+            is_const = true;
+        } else if !consume(tokens, idx, "volatile") {
+            return is_const;
+        }
+    }
+}
+
+// type_specifier = qualifiers base_type qualifiers
+fn type_specifier(tokens: &[Token], idx: &mut usize, context: &mut ParseContext) -> Type {
+    let is_const = qualifiers(tokens, idx);
+    let mut ty = base_type(tokens, idx, context);
+    ty.is_const |= qualifiers(tokens, idx) || is_const;
+    ty
+}
+
+// base_type = "void" | ("signed" | "unsigned")? ("char" | "int" | "long" "long"? "int"?)
+//           | "signed" | "unsigned" | "float" | "long"? "double"
+//           | struct_specifier | enum_specifier | typedef_name
+// a typedef name is resolved by the type checker
+fn base_type(tokens: &[Token], idx: &mut usize, context: &mut ParseContext) -> Type {
+    if let Some(Token {
+        kind: TokenKind::TkSigned | TokenKind::TkUnsigned,
+        val,
+    }) = tokens.get(*idx)
+    {
+        *idx += 1;
+        // `unsigned` alone means unsigned int
+        let mut ty = match tokens.get(*idx) {
+            Some(Token {
+                kind: TokenKind::TkChar | TokenKind::TkInt | TokenKind::TkLong,
+                ..
+            }) => base_type(tokens, idx, context),
+            _ => Type::new(TypeKind::TyInt),
+        };
+        ty.is_unsigned = val == "unsigned";
+        ty
+    } else if let Some(Token {
+        kind: TokenKind::TkStruct | TokenKind::TkUnion,
+        ..
+    }) = tokens.get(*idx)
+    {
+        struct_specifier(tokens, idx, context)
+    } else if let Some(Token {
+        kind: TokenKind::TkEnum,
+        ..
+    }) = tokens.get(*idx)
+    {
+        enum_specifier(tokens, idx, context)
+    } else if is_typedef_name(tokens, *idx, context) {
+        let mut ty = Type::new(TypeKind::TyTypedef);
+        ty.tag = identifier(tokens, idx);
+        ty
+    } else if consume(tokens, idx, "void") {
+        Type::new(TypeKind::TyVoid)
+    } else if consume(tokens, idx, "char") {
+        Type::new(TypeKind::TyChar)
+    } else if consume(tokens, idx, "int") {
+        Type::new(TypeKind::TyInt)
+    } else if consume(tokens, idx, "float") {
+        Type::new(TypeKind::TyFloat)
+    } else if consume(tokens, idx, "double") {
+        Type::new(TypeKind::TyDouble)
+    } else if consume(tokens, idx, "long") {
+        // long double is computed as double
+        if consume(tokens, idx, "double") {
+            return Type::new(TypeKind::TyDouble);
+        }
+        consume(tokens, idx, "long");
+        consume(tokens, idx, "int");
+        Type::new(TypeKind::TyLong)
+    } else {
+        panic!("Expected type name");
+    }
+}
+
+// struct_specifier = ("struct" | "union") ident? ("{" member_declaration* "}")?
+// member_declaration = type_specifier declarator ("," declarator)* ";"
+// the layout of the members is computed by the type checker
+fn struct_specifier(tokens: &[Token], idx: &mut usize, context: &mut ParseContext) -> Type {
+    let struct_id = *idx;
+    let mut ty = if consume(tokens, idx, "struct") {
+        Type::new(TypeKind::TyStruct)
+    } else {
+        expect(tokens, idx, "union");
+        Type::new(TypeKind::TyUnion)
+    };
+    if let Some(Token {
+        kind: TokenKind::TkVariable,
+        ..
+    }) = tokens.get(*idx)
+    {
+        ty.tag = identifier(tokens, idx);
+    }
+    if !consume(tokens, idx, "{") {
+        if ty.tag.is_empty() {
+            panic!("Expected tag or member list of {}", ty);
+        }
+        return ty;
+    }
+    ty.struct_id = Some(struct_id);
+    while !consume(tokens, idx, "}") {
+        let base = type_specifier(tokens, idx, context);
+        loop {
+            let (name, member_ty) = named_declarator(tokens, idx, context, base.clone());
+            ty.members.push(Member {
+                name,
+                ty: member_ty,
+                offset: 0,
+            });
+            if consume(tokens, idx, ";") {
+                break;
+            }
+            expect(tokens, idx, ",");
+        }
+    }
+    ty
+}
+
+// enum_specifier = "enum" ident? ("{" enumerator ("," enumerator)* ","? "}")?
+// enumerator = ident ("=" conditional)?
+// the values of the enumerators are computed by the type checker
+fn enum_specifier(tokens: &[Token], idx: &mut usize, context: &mut ParseContext) -> Type {
+    let enum_id = *idx;
+    expect(tokens, idx, "enum");
+    let mut ty = Type::new(TypeKind::TyEnum);
+    if let Some(Token {
+        kind: TokenKind::TkVariable,
+        ..
+    }) = tokens.get(*idx)
+    {
+        ty.tag = identifier(tokens, idx);
+    }
+    if !consume(tokens, idx, "{") {
+        if ty.tag.is_empty() {
+            panic!("Expected tag or enumerator list of {}", ty);
+        }
+        return ty;
+    }
+    ty.struct_id = Some(enum_id);
+    while !consume(tokens, idx, "}") {
+        let name = identifier(tokens, idx);
+        declare_name(context, &name, false);
+        let value = if consume(tokens, idx, "=") {
+            conditional(tokens, idx, context)
+        } else {
+            None
+        };
+        ty.enumerators.push(Enumerator { name, value });
+        if !consume(tokens, idx, ",") {
+            expect(tokens, idx, "}");
+            break;
+        }
+    }
+    ty
+}
+
+// pointers = ("*" qualifiers)*
+fn pointers(tokens: &[Token], idx: &mut usize, base: Type) -> Type {
+    let mut ty = base;
+    while consume(tokens, idx, "*") {
+        ty = Type::pointer_to(ty);
+        ty.is_const = qualifiers(tokens, idx);
+    }
+    ty
+}
+
+// declarator = pointers ("(" declarator ")" | ident?) type_suffix
+// The suffix applies to the type before a parenthesized declarator, which is then declared
+// as that type: `int (*f)(int)` reads `int (int)` first, so f is a pointer to a function.
+// the name is empty in an abstract declarator (e.g. `int (*)(int)` in a cast)
+fn declarator(
+    tokens: &[Token],
+    idx: &mut usize,
+    context: &mut ParseContext,
+    base: Type,
+) -> (String, Type) {
+    let ty = pointers(tokens, idx, base);
+    if is_nested_declarator(tokens, *idx, context) {
+        expect(tokens, idx, "(");
+        // the inner declarator is skipped until the type around it is known
+        let inner_idx = *idx;
+        declarator(tokens, idx, context, Type::new(TypeKind::TyLong));
+        expect(tokens, idx, ")");
+        let ty = type_suffix(tokens, idx, context, ty);
+        let end_idx = *idx;
+        *idx = inner_idx;
+        let declared = declarator(tokens, idx, context, ty);
+        *idx = end_idx;
+        return declared;
+    }
+    let name = match tokens.get(*idx) {
+        Some(Token {
+            kind: TokenKind::TkVariable,
+            ..
+        }) => identifier(tokens, idx),
+        _ => String::new(),
+    };
+    (name, type_suffix(tokens, idx, context, ty))
+}
+
+fn named_declarator(
+    tokens: &[Token],
+    idx: &mut usize,
+    context: &mut ParseContext,
+    base: Type,
+) -> (String, Type) {
+    let (name, ty) = declarator(tokens, idx, context, base);
+    if name.is_empty() {
+        panic!("Expected identifier");
+    }
+    (name, ty)
+}
+
+// A parenthesis after the pointers encloses a declarator unless it begins a parameter list
+// (e.g. `int (int)` or `int (T)` for a typedef name T), which starts with a type name or is empty.
+fn is_nested_declarator(tokens: &[Token], idx: usize, context: &ParseContext) -> bool {
+    matches!(tokens.get(idx), Some(token) if token.val == "(")
+        && match tokens.get(idx + 1) {
+            Some(token) => {
+                token.val == "*"
+                    || token.val == "("
+                    || matches!(token.kind, TokenKind::TkVariable)
+                        && !is_typedef_name(tokens, idx + 1, context)
+            }
+            _ => false,
+        }
+}
+
+// type_suffix = "[" conditional? "]" type_suffix | "(" params? ")" | ε
+// `int a[2][3]` is an array of two arrays of three ints, and `int f(int)` a function.
+// the length may be omitted where it is not needed (e.g. `int a[]` as a parameter)
+fn type_suffix(tokens: &[Token], idx: &mut usize, context: &mut ParseContext, base: Type) -> Type {
+    if consume(tokens, idx, "(") {
+        let (params, is_variadic) = params(tokens, idx, context);
+        let param_tys = params.into_iter().filter_map(|param| param.ty).collect();
+        let mut ty = Type::function_returning(base, param_tys);
+        ty.is_variadic = is_variadic;
+        return ty;
+    }
+    if !consume(tokens, idx, "[") {
+        return base;
+    }
+    if consume(tokens, idx, "]") {
+        return Type::array_of(type_suffix(tokens, idx, context, base), 0);
+    }
+    let len = conditional(tokens, idx, context);
+    expect(tokens, idx, "]");
+    let base = type_suffix(tokens, idx, context, base);
+    match len {
+        Some(node) if matches!(node.kind, NodeKind::NdNum) => {
+            Type::array_of(base, node.val.parse().unwrap())
+        }
+        _ => {
+            let mut ty = Type::array_of(base, 0);
+            ty.array_len_expr = len.map(Box::new);
+            ty
+        }
+    }
+}
+
+fn identifier(tokens: &[Token], idx: &mut usize) -> String {
+    match tokens.get(*idx) {
+        Some(Token {
+            kind: TokenKind::TkVariable,
+            val,
+        }) => {
+            *idx += 1;
+            val.clone()
+        }
+        _ => {
+            panic!("Expected identifier");
+        }
+    }
+}
+
+// params = ("void" | param ("," param)* ("," "...")?)? ")"
+// param = type_specifier declarator | ident
+// a missing type means long, which keeps the untyped programs working as before,
+// and `(void)` is an empty list.
+// returns the parameters and whether any number of arguments may follow them
+fn params(tokens: &[Token], idx: &mut usize, context: &mut ParseContext) -> (Vec<Node>, bool) {
+    let mut params = Vec::new();
+    if matches!(tokens.get(*idx + 1), Some(token) if token.val == ")") {
+        consume(tokens, idx, "void");
+    }
+    if consume(tokens, idx, ")") {
+        return (params, false);
+    }
+    loop {
+        // `...` is lexed as three dots
+        if consume(tokens, idx, ".") {
+            expect(tokens, idx, ".");
+            expect(tokens, idx, ".");
+            expect(tokens, idx, ")");
+            return (params, true);
+        }
+        let param = if is_type_name(tokens, *idx, context) {
+            let base = type_specifier(tokens, idx, context);
+            // parameter names may be omitted in a declaration (e.g. `int putchar(int);`),
+            // and an array or function parameter is passed as a pointer
+            let (name, ty) = declarator(tokens, idx, context, base);
+            let mut param = Node::new(&name, NodeKind::NdVariable);
+            param.ty = Some(ty.decay());
+            param
+        } else {
+            let name = identifier(tokens, idx);
+            let mut param = Node::new(&name, NodeKind::NdVariable);
+            param.ty = Some(Type::new(TypeKind::TyLong));
+            param
+        };
+        params.push(param);
+        if consume(tokens, idx, ")") {
+            return (params, false);
+        }
+        expect(tokens, idx, ",");
+    }
+}
+
+// function_definition = storage_class (type_specifier pointers)? ident "(" params ("{" stmt* "}" | ";")
+fn function_definition(
+    tokens: &[Token],
+    idx: &mut usize,
+    context: &mut ParseContext,
+) -> Option<Node> {
+    let storage_class = storage_class(tokens, idx);
+    let return_ty = if is_type_name(tokens, *idx, context) {
+        let base = type_specifier(tokens, idx, context);
+        pointers(tokens, idx, base)
+    } else {
+        Type::new(TypeKind::TyLong)
+    };
+    let name = identifier(tokens, idx);
+    declare_name(context, &name, false);
+    let mut node = create_node(&name, NodeKind::NdFunction, None, None)?;
+    node.ty = Some(return_ty);
+    node.storage_class = storage_class;
+    expect(tokens, idx, "(");
+    let (params, is_variadic) = params(tokens, idx, context);
+    if is_variadic {
+        node.fixed_arg_count = Some(params.len());
+    }
+    node.params = params;
+    if consume(tokens, idx, ";") {
+        // a function declaration only tells that the function is defined elsewhere
+        // (e.g. in libc), so nothing is generated for it
+        node.kind = NodeKind::NdFunctionDeclaration;
+        return Some(node);
+    }
+    if node.storage_class == StorageClass::ScExtern {
+        panic!("extern function {} cannot have a body", name);
+    }
+    expect(tokens, idx, "{");
+    // the parameters are in the scope of the body
+    enter_scope(context);
+    for param in node.params.iter() {
+        declare_name(context, &param.val, false);
+    }
+    while !consume(tokens, idx, "}") {
+        match stmt(tokens, idx, context) {
             Some(node_) => {
-                vec.push(Some(node_));
-                continue;
+                node.body.push(node_);
             }
             _ => {
-                vec.push(None);
-                break;
+                panic!("Expected {}", "}");
             }
         }
     }
-    vec
+    leave_scope(context);
+    Some(node)
 }
 
-fn stmt(tokens: &Vec<Token>, idx: &mut usize) -> Option<Node> {
+fn stmt(tokens: &[Token], idx: &mut usize, context: &mut ParseContext) -> Option<Node> {
     let node;
-    if consume(&tokens, idx, &"return") {
-        node = expr(&tokens, idx);
-        if consume(&tokens, idx, &";") {
-            return create_node(&"return", NodeKind::NdReturn, node, None);
+    if is_type_name(tokens, *idx, context) {
+        declaration(tokens, idx, context)
+    } else if consume(tokens, idx, "{") {
+        let mut node = create_node("block", NodeKind::NdBlock, None, None)?;
+        enter_scope(context);
+        while !consume(tokens, idx, "}") {
+            match stmt(tokens, idx, context) {
+                Some(node_) => {
+                    node.body.push(node_);
+                }
+                _ => {
+                    panic!("Expected {}", "}");
+                }
+            }
+        }
+        leave_scope(context);
+        Some(node)
+    } else if consume(tokens, idx, "if") {
+        expect(tokens, idx, "(");
+        let cond = expr(tokens, idx, context);
+        expect(tokens, idx, ")");
+        let then = stmt(tokens, idx, context);
+        if then.is_none() {
+            panic!("Expected statement after if");
+        }
+        let els = if consume(tokens, idx, "else") {
+            let els = stmt(tokens, idx, context);
+            if els.is_none() {
+                panic!("Expected statement after else");
+            }
+            els
+        } else {
+            None
+        };
+        create_if_node(cond, then, els)
+    } else if consume(tokens, idx, "while") {
+        expect(tokens, idx, "(");
+        let cond = expr(tokens, idx, context);
+        expect(tokens, idx, ")");
+        let then = stmt(tokens, idx, context);
+        if then.is_none() {
+            panic!("Expected statement after while");
+        }
+        create_while_node(cond, then)
+    } else if consume(tokens, idx, "for") {
+        expect(tokens, idx, "(");
+        // a variable declared in the initialization is visible only inside the loop
+        enter_scope(context);
+        // the values of the initialization and the step are discarded like expression statements
+        let init = if is_type_name(tokens, *idx, context) {
+            declaration(tokens, idx, context)
+        } else {
+            let init = create_node(
+                "expression statement",
+                NodeKind::NdExpressionStatement,
+                expr(tokens, idx, context),
+                None,
+            );
+            expect(tokens, idx, ";");
+            init
+        };
+        let cond = expr(tokens, idx, context);
+        expect(tokens, idx, ";");
+        let inc = create_node(
+            "expression statement",
+            NodeKind::NdExpressionStatement,
+            expr(tokens, idx, context),
+            None,
+        );
+        expect(tokens, idx, ")");
+        let then = stmt(tokens, idx, context);
+        if then.is_none() {
+            panic!("Expected statement after for");
+        }
+        leave_scope(context);
+        create_for_node(init, cond, inc, then)
+    } else if consume(tokens, idx, "do") {
+        let then = stmt(tokens, idx, context);
+        if then.is_none() {
+            panic!("Expected statement after do");
+        }
+        expect(tokens, idx, "while");
+        expect(tokens, idx, "(");
+        let cond = expr(tokens, idx, context);
+        expect(tokens, idx, ")");
+        expect(tokens, idx, ";");
+        let mut node = create_node("do", NodeKind::NdDoWhile, None, None)?;
+        node.cond = cond.map(Box::new);
+        node.then = then.map(Box::new);
+        Some(node)
+    } else if consume(tokens, idx, "goto") {
+        let name = identifier(tokens, idx);
+        expect(tokens, idx, ";");
+        create_node(&name, NodeKind::NdGoto, None, None)
+    } else if matches!(tokens.get(*idx), Some(token) if matches!(token.kind, TokenKind::TkVariable))
+        && matches!(tokens.get(*idx + 1), Some(token) if token.val == ":")
+    {
+        let name = identifier(tokens, idx);
+        expect(tokens, idx, ":");
+        let then = stmt(tokens, idx, context);
+        if then.is_none() {
+            panic!("Expected statement after label {}", name);
+        }
+        let mut node = create_node(&name, NodeKind::NdLabel, None, None)?;
+        node.then = then.map(Box::new);
+        Some(node)
+    } else if consume(tokens, idx, "switch") {
+        expect(tokens, idx, "(");
+        let cond = expr(tokens, idx, context);
+        expect(tokens, idx, ")");
+        let then = stmt(tokens, idx, context);
+        if then.is_none() {
+            panic!("Expected statement after switch");
+        }
+        let mut node = create_node("switch", NodeKind::NdSwitch, None, None)?;
+        node.cond = cond.map(Box::new);
+        node.then = then.map(Box::new);
+        Some(node)
+    } else if let Some(TokenKind::TkCase | TokenKind::TkDefault) =
+        tokens.get(*idx).map(|token| &token.kind)
+    {
+        // the token index of a case label names it uniquely, as `.L.case.<idx>`
+        let case_idx = *idx;
+        *idx += 1;
+        let value = if let TokenKind::TkCase = tokens[case_idx].kind {
+            conditional(tokens, idx, context)
+        } else {
+            None
+        };
+        expect(tokens, idx, ":");
+        let then = stmt(tokens, idx, context);
+        if then.is_none() {
+            panic!("Expected statement after {}", tokens[case_idx].val);
+        }
+        let mut node = create_node(&case_idx.to_string(), NodeKind::NdCase, value, None)?;
+        node.then = then.map(Box::new);
+        Some(node)
+    } else if consume(tokens, idx, "break") {
+        expect(tokens, idx, ";");
+        create_node("break", NodeKind::NdBreak, None, None)
+    } else if consume(tokens, idx, "continue") {
+        expect(tokens, idx, ";");
+        create_node("continue", NodeKind::NdContinue, None, None)
+    } else if consume(tokens, idx, "return") {
+        node = expr(tokens, idx, context);
+        if consume(tokens, idx, ";") {
+            create_node("return", NodeKind::NdReturn, node, None)
         } else {
             None
         }
     } else {
-        let node = expr(&tokens, idx);
-        if consume(&tokens, idx, &";") {
-            node
+        let node = expr(tokens, idx, context);
+        if consume(tokens, idx, ";") {
+            create_node(
+                "expression statement",
+                NodeKind::NdExpressionStatement,
+                node,
+                None,
+            )
         } else {
             None
         }
     }
 }
 
-fn expr(tokens: &Vec<Token>, idx: &mut usize) -> Option<Node> {
-    assign(&tokens, idx)
+// declaration = ("typedef" | storage_class) type_specifier (declarator ("," declarator)*)? ";"
+// the declarators of a typedef name types instead of variables,
+// and each of the others may be followed by "=" initializer
+fn declaration(tokens: &[Token], idx: &mut usize, context: &mut ParseContext) -> Option<Node> {
+    let mut node = create_node("declaration", NodeKind::NdDeclaration, None, None)?;
+    let is_typedef = consume(tokens, idx, "typedef");
+    if !is_typedef {
+        node.storage_class = storage_class(tokens, idx);
+    }
+    if is_typedef {
+        node.kind = NodeKind::NdTypedef;
+    }
+    let base = type_specifier(tokens, idx, context);
+    // the type is kept for a declaration without declarators such as `struct P { int x; };`
+    node.ty = Some(base.clone());
+    if consume(tokens, idx, ";") {
+        return Some(node);
+    }
+    loop {
+        let (name, ty) = named_declarator(tokens, idx, context, base.clone());
+        // the name is in scope from its declarator on, even in its own initializer
+        declare_name(context, &name, is_typedef);
+        let mut declarator = create_node(&name, NodeKind::NdVariable, None, None)?;
+        declarator.ty = Some(ty);
+        if !is_typedef && consume(tokens, idx, "=") {
+            if node.storage_class == StorageClass::ScExtern {
+                panic!("extern variable {} cannot have an initializer", name);
+            }
+            if matches!(tokens.get(*idx), Some(token) if token.val == "{") {
+                // an initializer list is flattened by the type checker, which knows the layout
+                declarator.init = initializer(tokens, idx, context).map(Box::new);
+            } else {
+                // an initializer is lowered to an assignment right after the variable comes into scope
+                let variable = create_node(&declarator.val, NodeKind::NdVariable, None, None);
+                let assignment = create_node(
+                    "=",
+                    NodeKind::NdAssignOperator,
+                    variable,
+                    assign(tokens, idx, context),
+                );
+                declarator.init = create_node(
+                    "expression statement",
+                    NodeKind::NdExpressionStatement,
+                    assignment,
+                    None,
+                )
+                .map(Box::new);
+            }
+        }
+        node.body.push(declarator);
+        if consume(tokens, idx, ";") {
+            return Some(node);
+        }
+        expect(tokens, idx, ",");
+    }
 }
 
-fn assign(tokens: &Vec<Token>, idx: &mut usize) -> Option<Node> {
-    let node = equality(&tokens, idx);
+// initializer = "{" (designation ("," designation)* ","?)? "}" | assign
+fn initializer(tokens: &[Token], idx: &mut usize, context: &mut ParseContext) -> Option<Node> {
+    if !consume(tokens, idx, "{") {
+        return assign(tokens, idx, context);
+    }
+    let mut node = create_node("initializer", NodeKind::NdInitializer, None, None)?;
+    while !consume(tokens, idx, "}") {
+        match designation(tokens, idx, context) {
+            Some(element) => {
+                node.body.push(element);
+            }
+            _ => {
+                panic!("Expected initializer");
+            }
+        }
+        if !consume(tokens, idx, ",") {
+            expect(tokens, idx, "}");
+            break;
+        }
+    }
+    Some(node)
+}
 
-    if consume(&tokens, idx, &"=") {
-        return create_node(
-            &"=",
-            NodeKind::NdAssignOperator,
+// designation = ("[" conditional "]" | "." ident) (designation | "=" initializer) | initializer
+// a designator keeps the index in `cond` (or the member in `val`) and the rest in `left`,
+// so `.a[1] = 2` chooses the element 1 of the member a
+fn designation(tokens: &[Token], idx: &mut usize, context: &mut ParseContext) -> Option<Node> {
+    let mut node = if consume(tokens, idx, "[") {
+        let mut node = create_node("[]", NodeKind::NdDesignator, None, None)?;
+        node.cond = conditional(tokens, idx, context).map(Box::new);
+        expect(tokens, idx, "]");
+        node
+    } else if consume(tokens, idx, ".") {
+        create_node(&identifier(tokens, idx), NodeKind::NdDesignator, None, None)?
+    } else {
+        return initializer(tokens, idx, context);
+    };
+    node.left =
+        if matches!(tokens.get(*idx), Some(token) if token.val == "[" || token.val == ".") {
+            designation(tokens, idx, context)
+        } else {
+            expect(tokens, idx, "=");
+            initializer(tokens, idx, context)
+        }
+        .map(Box::new);
+    Some(node)
+}
+
+// expr = assign ("," assign)*
+fn expr(tokens: &[Token], idx: &mut usize, context: &mut ParseContext) -> Option<Node> {
+    let mut node = assign(tokens, idx, context);
+
+    while consume(tokens, idx, ",") {
+        node = create_node(",", NodeKind::NdComma, node, assign(tokens, idx, context));
+    }
+    node
+}
+
+// assign = conditional (("=" | "+=" | "-=" | "*=" | "/=" | "%=" | "&=" | "|=" | "^=" | "<<=" | ">>=") assign)?
+// assignment is right-associative, so `a = b = 3` assigns 3 to both
+fn assign(tokens: &[Token], idx: &mut usize, context: &mut ParseContext) -> Option<Node> {
+    let node = conditional(tokens, idx, context);
+
+    for op in [
+        "=", "+=", "-=", "*=", "/=", "%=", "&=", "|=", "^=", "<<=", ">>=",
+    ] {
+        if consume(tokens, idx, op) {
+            return create_node(
+                op,
+                NodeKind::NdAssignOperator,
+                node,
+                assign(tokens, idx, context),
+            );
+        }
+    }
+    node
+}
+
+// conditional = logor ("?" expr ":" conditional)?
+fn conditional(tokens: &[Token], idx: &mut usize, context: &mut ParseContext) -> Option<Node> {
+    let cond = logor(tokens, idx, context);
+
+    if !consume(tokens, idx, "?") {
+        return cond;
+    }
+    let then = expr(tokens, idx, context);
+    expect(tokens, idx, ":");
+    let els = conditional(tokens, idx, context);
+    let mut node = create_node("?:", NodeKind::NdConditional, None, None)?;
+    node.cond = cond.map(Box::new);
+    node.then = then.map(Box::new);
+    node.els = els.map(Box::new);
+    Some(node)
+}
+
+// logor = logand ("||" logand)*
+fn logor(tokens: &[Token], idx: &mut usize, context: &mut ParseContext) -> Option<Node> {
+    let mut node = logand(tokens, idx, context);
+
+    while consume(tokens, idx, "||") {
+        node = create_node(
+            "||",
+            NodeKind::NdLogicalOperator,
             node,
-            equality(&tokens, idx),
+            logand(tokens, idx, context),
+        );
+    }
+    node
+}
+
+// logand = bitor ("&&" bitor)*
+fn logand(tokens: &[Token], idx: &mut usize, context: &mut ParseContext) -> Option<Node> {
+    let mut node = bitor(tokens, idx, context);
+
+    while consume(tokens, idx, "&&") {
+        node = create_node(
+            "&&",
+            NodeKind::NdLogicalOperator,
+            node,
+            bitor(tokens, idx, context),
+        );
+    }
+    node
+}
+
+// bitor = bitxor ("|" bitxor)*
+fn bitor(tokens: &[Token], idx: &mut usize, context: &mut ParseContext) -> Option<Node> {
+    let mut node = bitxor(tokens, idx, context);
+
+    while consume(tokens, idx, "|") {
+        node = create_node(
+            "|",
+            NodeKind::NdOperator,
+            node,
+            bitxor(tokens, idx, context),
+        );
+    }
+    node
+}
+
+// bitxor = bitand ("^" bitand)*
+fn bitxor(tokens: &[Token], idx: &mut usize, context: &mut ParseContext) -> Option<Node> {
+    let mut node = bitand(tokens, idx, context);
+
+    while consume(tokens, idx, "^") {
+        node = create_node(
+            "^",
+            NodeKind::NdOperator,
+            node,
+            bitand(tokens, idx, context),
         );
-    } else {
-        node
     }
+    node
 }
 
-fn equality(tokens: &Vec<Token>, idx: &mut usize) -> Option<Node> {
-    let mut node = addsub(&tokens, idx);
+// bitand = equality ("&" equality)*
+fn bitand(tokens: &[Token], idx: &mut usize, context: &mut ParseContext) -> Option<Node> {
+    let mut node = equality(tokens, idx, context);
+
+    while consume(tokens, idx, "&") {
+        node = create_node(
+            "&",
+            NodeKind::NdOperator,
+            node,
+            equality(tokens, idx, context),
+        );
+    }
+    node
+}
+
+fn equality(tokens: &[Token], idx: &mut usize, context: &mut ParseContext) -> Option<Node> {
+    let mut node = relational(tokens, idx, context);
 
     loop {
-        if consume(&tokens, idx, &"==") {
+        if consume(tokens, idx, "==") {
             node = create_node(
-                &"==",
+                "==",
                 NodeKind::NdComparisonOperator,
                 node,
-                addsub(&tokens, idx),
+                relational(tokens, idx, context),
             );
-        } else if consume(&tokens, idx, &"!=") {
+        } else if consume(tokens, idx, "!=") {
             node = create_node(
-                &"!=",
+                "!=",
                 NodeKind::NdComparisonOperator,
                 node,
-                addsub(&tokens, idx),
+                relational(tokens, idx, context),
             );
-        } else if consume(&tokens, idx, &"<") {
+        } else {
+            return node;
+        }
+    }
+}
+
+fn relational(tokens: &[Token], idx: &mut usize, context: &mut ParseContext) -> Option<Node> {
+    let mut node = shift(tokens, idx, context);
+
+    loop {
+        if consume(tokens, idx, "<") {
             node = create_node(
-                &"<",
+                "<",
                 NodeKind::NdComparisonOperator,
                 node,
-                addsub(&tokens, idx),
+                shift(tokens, idx, context),
             );
-        } else if consume(&tokens, idx, &"<=") {
+        } else if consume(tokens, idx, "<=") {
             node = create_node(
-                &"<=",
+                "<=",
                 NodeKind::NdComparisonOperator,
                 node,
-                addsub(&tokens, idx),
+                shift(tokens, idx, context),
             );
-        } else if consume(&tokens, idx, &">") {
+        } else if consume(tokens, idx, ">") {
             node = create_node(
-                &"<",
+                "<",
                 NodeKind::NdComparisonOperator,
-                addsub(&tokens, idx),
+                shift(tokens, idx, context),
                 node,
             );
-        } else if consume(&tokens, idx, &">=") {
+        } else if consume(tokens, idx, ">=") {
             node = create_node(
-                &"<=",
+                "<=",
                 NodeKind::NdComparisonOperator,
-                addsub(&tokens, idx),
+                shift(tokens, idx, context),
                 node,
             );
         } else {
@@ -158,47 +1102,201 @@ fn equality(tokens: &Vec<Token>, idx: &mut usize) -> Option<Node> {
     }
 }
 
-fn addsub(tokens: &Vec<Token>, idx: &mut usize) -> Option<Node> {
-    let mut node = term(&tokens, idx);
+// shift = addsub ("<<" addsub | ">>" addsub)*
+fn shift(tokens: &[Token], idx: &mut usize, context: &mut ParseContext) -> Option<Node> {
+    let mut node = addsub(tokens, idx, context);
 
     loop {
-        if consume(&tokens, idx, &"+") {
-            node = create_node(&"+", NodeKind::NdOperator, node, term(&tokens, idx));
-        } else if consume(&tokens, idx, &"-") {
-            node = create_node(&"-", NodeKind::NdOperator, node, term(&tokens, idx));
+        if consume(tokens, idx, "<<") {
+            node = create_node(
+                "<<",
+                NodeKind::NdOperator,
+                node,
+                addsub(tokens, idx, context),
+            );
+        } else if consume(tokens, idx, ">>") {
+            node = create_node(
+                ">>",
+                NodeKind::NdOperator,
+                node,
+                addsub(tokens, idx, context),
+            );
         } else {
             return node;
         }
     }
 }
 
-fn term(tokens: &Vec<Token>, idx: &mut usize) -> Option<Node> {
-    let mut node = unary(&tokens, idx);
+fn addsub(tokens: &[Token], idx: &mut usize, context: &mut ParseContext) -> Option<Node> {
+    let mut node = term(tokens, idx, context);
 
     loop {
-        if consume(&tokens, idx, &"*") {
-            node = create_node(&"*", NodeKind::NdOperator, node, unary(&tokens, idx));
-        } else if consume(&tokens, idx, &"/") {
-            node = create_node(&"/", NodeKind::NdOperator, node, unary(&tokens, idx));
+        if consume(tokens, idx, "+") {
+            node = create_node("+", NodeKind::NdOperator, node, term(tokens, idx, context));
+        } else if consume(tokens, idx, "-") {
+            node = create_node("-", NodeKind::NdOperator, node, term(tokens, idx, context));
         } else {
             return node;
         }
     }
 }
 
-fn unary(tokens: &Vec<Token>, idx: &mut usize) -> Option<Node> {
-    if consume(&tokens, idx, &"+") {
-        let node = create_node("0", NodeKind::NdNum, None, None);
-        return create_node(&"+", NodeKind::NdOperator, node, factor(&tokens, idx));
-    } else if consume(&tokens, idx, &"-") {
+fn term(tokens: &[Token], idx: &mut usize, context: &mut ParseContext) -> Option<Node> {
+    let mut node = unary(tokens, idx, context);
+
+    loop {
+        if consume(tokens, idx, "*") {
+            node = create_node("*", NodeKind::NdOperator, node, unary(tokens, idx, context));
+        } else if consume(tokens, idx, "/") {
+            node = create_node("/", NodeKind::NdOperator, node, unary(tokens, idx, context));
+        } else if consume(tokens, idx, "%") {
+            node = create_node("%", NodeKind::NdOperator, node, unary(tokens, idx, context));
+        } else {
+            return node;
+        }
+    }
+}
+
+// unary = ("+" | "-" | "&" | "*" | "!" | "~" | "++" | "--" | "sizeof") unary
+//       | "sizeof" "(" type_name ")"
+//       | "(" type_name ")" unary
+//       | postfix
+fn unary(tokens: &[Token], idx: &mut usize, context: &mut ParseContext) -> Option<Node> {
+    if consume(tokens, idx, "+") {
+        create_node(
+            "+",
+            NodeKind::NdUnaryOperator,
+            unary(tokens, idx, context),
+            None,
+        )
+    } else if consume(tokens, idx, "-") {
+        create_node(
+            "-",
+            NodeKind::NdUnaryOperator,
+            unary(tokens, idx, context),
+            None,
+        )
+    } else if consume(tokens, idx, "&") {
+        create_node("&", NodeKind::NdAddress, unary(tokens, idx, context), None)
+    } else if consume(tokens, idx, "*") {
+        create_node(
+            "*",
+            NodeKind::NdDereference,
+            unary(tokens, idx, context),
+            None,
+        )
+    } else if consume(tokens, idx, "!") {
+        // `!x` is lowered to `x == 0`
         let node = create_node("0", NodeKind::NdNum, None, None);
-        return create_node(&"-", NodeKind::NdOperator, node, factor(&tokens, idx));
+        create_node(
+            "==",
+            NodeKind::NdComparisonOperator,
+            unary(tokens, idx, context),
+            node,
+        )
+    } else if consume(tokens, idx, "++") {
+        // `++a` is lowered to `a += 1`
+        let node = create_node("1", NodeKind::NdNum, None, None);
+        create_node(
+            "+=",
+            NodeKind::NdAssignOperator,
+            unary(tokens, idx, context),
+            node,
+        )
+    } else if consume(tokens, idx, "--") {
+        let node = create_node("1", NodeKind::NdNum, None, None);
+        create_node(
+            "-=",
+            NodeKind::NdAssignOperator,
+            unary(tokens, idx, context),
+            node,
+        )
+    } else if consume(tokens, idx, "~") {
+        create_node(
+            "~",
+            NodeKind::NdBitwiseNot,
+            unary(tokens, idx, context),
+            None,
+        )
+    } else if consume(tokens, idx, "sizeof") {
+        if is_parenthesized_type_name(tokens, *idx, context) {
+            // the type is kept in the node until the type checker replaces it with its size
+            expect(tokens, idx, "(");
+            let mut node = create_node("sizeof", NodeKind::NdSizeof, None, None)?;
+            node.ty = Some(type_name(tokens, idx, context));
+            expect(tokens, idx, ")");
+            Some(node)
+        } else {
+            create_node(
+                "sizeof",
+                NodeKind::NdSizeof,
+                unary(tokens, idx, context),
+                None,
+            )
+        }
+    } else if is_parenthesized_type_name(tokens, *idx, context) {
+        expect(tokens, idx, "(");
+        let ty = type_name(tokens, idx, context);
+        expect(tokens, idx, ")");
+        let mut node = create_node("cast", NodeKind::NdCast, unary(tokens, idx, context), None)?;
+        node.ty = Some(ty);
+        Some(node)
     } else {
-        return factor(&tokens, idx);
+        postfix(tokens, idx, context)
+    }
+}
+
+fn is_parenthesized_type_name(tokens: &[Token], idx: usize, context: &ParseContext) -> bool {
+    matches!(tokens.get(idx), Some(token) if token.val == "(")
+        && is_type_name(tokens, idx + 1, context)
+}
+
+// type_name = type_specifier declarator
+// a type without a name, as in a cast or sizeof
+fn type_name(tokens: &[Token], idx: &mut usize, context: &mut ParseContext) -> Type {
+    let base = type_specifier(tokens, idx, context);
+    let (name, ty) = declarator(tokens, idx, context, base);
+    if !name.is_empty() {
+        panic!("Unexpected identifier {} in type name", name);
     }
+    ty
 }
 
-fn factor(tokens: &Vec<Token>, idx: &mut usize) -> Option<Node> {
+// postfix = factor ("[" expr "]" | "." ident | "->" ident | "++" | "--" | "(" arguments)*
+// `a[i]` is lowered to `*(a + i)` and `p->m` to `(*p).m`
+fn postfix(tokens: &[Token], idx: &mut usize, context: &mut ParseContext) -> Option<Node> {
+    let mut node = factor(tokens, idx, context);
+
+    loop {
+        if consume(tokens, idx, "(") {
+            // a call through a function pointer (e.g. `(*f)(1)` or `ops[i](1)`) keeps the callee
+            // in `left`, while a call by name is parsed by factor
+            let mut call = create_node("call", NodeKind::NdCall, node, None)?;
+            call.args = arguments(tokens, idx, context, "function pointer");
+            node = Some(call);
+        } else if consume(tokens, idx, "[") {
+            let index = expr(tokens, idx, context);
+            expect(tokens, idx, "]");
+            let address = create_node("+", NodeKind::NdOperator, node, index);
+            node = create_node("*", NodeKind::NdDereference, address, None);
+        } else if consume(tokens, idx, ".") {
+            let name = identifier(tokens, idx);
+            node = create_node(&name, NodeKind::NdMember, node, None);
+        } else if consume(tokens, idx, "->") {
+            let name = identifier(tokens, idx);
+            let dereference = create_node("*", NodeKind::NdDereference, node, None);
+            node = create_node(&name, NodeKind::NdMember, dereference, None);
+        } else if consume(tokens, idx, "++") {
+            node = create_node("++", NodeKind::NdPostfixOperator, node, None);
+        } else if consume(tokens, idx, "--") {
+            node = create_node("--", NodeKind::NdPostfixOperator, node, None);
+        } else {
+            return node;
+        }
+    }
+}
+
+fn factor(tokens: &[Token], idx: &mut usize, context: &mut ParseContext) -> Option<Node> {
     let idx_: usize = *idx;
     if tokens.len() <= idx_ {
         return None;
@@ -206,27 +1304,132 @@ fn factor(tokens: &Vec<Token>, idx: &mut usize) -> Option<Node> {
     match tokens[idx_].kind {
         TokenKind::TkNum => {
             *idx += 1;
-            return create_node(&tokens[idx_].val, NodeKind::NdNum, None, None);
-        }
-        TokenKind::TkParenthesis => {
-            if &tokens[idx_].val[..] == "(" {
-                *idx += 1;
-                let node = expr(&tokens, idx);
-                if consume(&tokens, idx, &")") {
-                    return node;
+            let val = &tokens[idx_].val;
+            // a floating-point literal is double unless it has the suffix f (e.g. `1.5f`)
+            if val.contains(&['.', 'e', 'E'][..]) {
+                let digits = val.trim_end_matches(&['f', 'F', 'l', 'L'][..]);
+                let kind = if val.ends_with(&['f', 'F'][..]) {
+                    TypeKind::TyFloat
                 } else {
-                    panic!("Expected )");
+                    TypeKind::TyDouble
+                };
+                if digits.parse::<f64>().is_err() {
+                    panic!("Invalid floating constant {}", val);
                 }
+                let mut node = create_node(digits, NodeKind::NdNum, None, None)?;
+                node.ty = Some(Type::new(kind));
+                return Some(node);
+            }
+            // a suffix gives the least type of the literal, which the type checker may widen
+            let digits = val.trim_end_matches(&['u', 'U', 'l', 'L'][..]);
+            let suffix = val[digits.len()..].to_lowercase();
+            let mut node = create_node(digits, NodeKind::NdNum, None, None)?;
+            if !suffix.is_empty() {
+                let kind = if suffix.contains('l') {
+                    TypeKind::TyLong
+                } else {
+                    TypeKind::TyInt
+                };
+                let mut ty = Type::new(kind);
+                ty.is_unsigned = suffix.contains('u');
+                node.ty = Some(ty);
+            }
+            Some(node)
+        }
+        TokenKind::TkString => {
+            *idx += 1;
+            create_node(&tokens[idx_].val, NodeKind::NdString, None, None)
+        }
+        TokenKind::TkParenthesis if &tokens[idx_].val[..] == "(" => {
+            *idx += 1;
+            let node = expr(tokens, idx, context);
+            if consume(tokens, idx, ")") {
+                node
             } else {
-                return None;
+                panic!("Expected )");
             }
         }
         TokenKind::TkVariable => {
             *idx += 1;
-            return create_node(&tokens[idx_].val, NodeKind::NdVariable, None, None);
+            if consume(tokens, idx, "(") {
+                let name = &tokens[idx_].val[..];
+                if let "va_start" | "va_arg" | "va_end" = name {
+                    return stdarg(tokens, idx, context, name);
+                }
+                let mut node = create_node(name, NodeKind::NdCall, None, None)?;
+                node.args = arguments(tokens, idx, context, name);
+                Some(node)
+            } else {
+                create_node(&tokens[idx_].val, NodeKind::NdVariable, None, None)
+            }
+        }
+        _ => None,
+    }
+}
+
+// stdarg = "va_start" "(" assign "," ident ")"
+//        | "va_arg" "(" assign "," type_name ")"
+//        | "va_end" "(" assign ")"
+// a va_list points at the next variadic argument in memory, where every argument takes 8 bytes.
+// `va_start(ap, last)` is lowered to `ap = <the first variadic argument>`,
+// `va_arg(ap, T)` to `*(T *)((ap += 8) - 8)` and `va_end(ap)` to `ap`
+fn stdarg(
+    tokens: &[Token],
+    idx: &mut usize,
+    context: &mut ParseContext,
+    name: &str,
+) -> Option<Node> {
+    let ap = assign(tokens, idx, context);
+    match name {
+        "va_start" => {
+            expect(tokens, idx, ",");
+            identifier(tokens, idx);
+            expect(tokens, idx, ")");
+            let start = create_node(name, NodeKind::NdVaStart, None, None);
+            create_node("=", NodeKind::NdAssignOperator, ap, start)
+        }
+        "va_arg" => {
+            expect(tokens, idx, ",");
+            let ty = type_name(tokens, idx, context);
+            expect(tokens, idx, ")");
+            let size = create_node("8", NodeKind::NdNum, None, None);
+            let next = create_node("+=", NodeKind::NdAssignOperator, ap, size);
+            let size = create_node("8", NodeKind::NdNum, None, None);
+            let arg = create_node("-", NodeKind::NdOperator, next, size);
+            let mut pointer = create_node("cast", NodeKind::NdCast, arg, None)?;
+            pointer.ty = Some(Type::pointer_to(ty));
+            create_node("*", NodeKind::NdDereference, Some(pointer), None)
         }
         _ => {
-            return None;
+            expect(tokens, idx, ")");
+            ap
+        }
+    }
+}
+
+// arguments = (assign ("," assign)*)? ")"
+fn arguments(
+    tokens: &[Token],
+    idx: &mut usize,
+    context: &mut ParseContext,
+    callee: &str,
+) -> Vec<Node> {
+    let mut args = Vec::new();
+    if consume(tokens, idx, ")") {
+        return args;
+    }
+    loop {
+        match assign(tokens, idx, context) {
+            Some(arg) => {
+                args.push(arg);
+            }
+            _ => {
+                panic!("Expected argument of {}", callee);
+            }
+        }
+        if consume(tokens, idx, ")") {
+            return args;
         }
+        expect(tokens, idx, ",");
     }
 }
@@ -0,0 +1,1517 @@
+use crate::compiler::*;
+use std::collections::{HashMap, HashSet};
+
+struct TypeContext {
+    // one map per lexical scope (innermost last), following the same scoping rules
+    // as the frame layout in the native code generator
+    variable_maps: Vec<HashMap<String, Type>>,
+    // struct and union tags follow the same scopes and map to the ids of their definitions
+    tag_maps: Vec<HashMap<String, usize>>,
+    // typedef names and enumerators are ordinary identifiers scoped like variables
+    typedef_maps: Vec<HashMap<String, Type>>,
+    constant_maps: Vec<HashMap<String, i64>>,
+    // the labels of the static local variables by their names, scoped like variables
+    static_maps: Vec<HashMap<String, String>>,
+    // the global variables that are defined with an initializer
+    initialized_variables: HashSet<String>,
+    // the definitions of structs and unions, and the enums whose enumerators are defined
+    structs: HashMap<usize, Type>,
+    // the types of the functions by their names
+    functions: HashMap<String, Type>,
+    // the return type of the function being checked and whether it is variadic
+    return_ty: Type,
+    is_variadic: bool,
+    // counts the hidden variables introduced while lowering compound assignments
+    temporary_count: usize,
+}
+
+pub fn type_checking(ast: &mut [Option<Node>]) {
+    // the outermost scope holds the global variables
+    let mut context = TypeContext {
+        variable_maps: vec![HashMap::new()],
+        tag_maps: vec![HashMap::new()],
+        // a va_list points at the next variadic argument (see stdarg in the parser)
+        typedef_maps: vec![HashMap::from([(
+            String::from("va_list"),
+            Type::pointer_to(Type::new(TypeKind::TyChar)),
+        )])],
+        constant_maps: vec![HashMap::new()],
+        static_maps: vec![HashMap::new()],
+        initialized_variables: HashSet::new(),
+        structs: HashMap::new(),
+        functions: HashMap::new(),
+        return_ty: Type::new(TypeKind::TyLong),
+        is_variadic: false,
+        temporary_count: 0,
+    };
+
+    // functions may be called before they appear, so the type of every function is collected first
+    for node in ast.iter().flatten() {
+        if let NodeKind::NdFunction | NodeKind::NdFunctionDeclaration = node.kind {
+            let function_ty = function_type(node);
+            if let Some(declared) = context.functions.get(&node.val) {
+                if *declared != function_ty {
+                    panic!("Type error: conflicting types for {}", node.val);
+                }
+            }
+            context.functions.insert(node.val.clone(), function_ty);
+        }
+    }
+
+    for node in ast.iter_mut().flatten() {
+        check(node, &mut context);
+    }
+
+    // the declarations of a global variable without an initializer are tentative definitions,
+    // so the variable is defined once: by its initializer if it has one, or as zero
+    let mut defined_variables = context.initialized_variables;
+    for node in ast.iter_mut().flatten() {
+        if matches!(node.kind, NodeKind::NdDeclaration)
+            && node.storage_class != StorageClass::ScExtern
+        {
+            node.body.retain(|declarator| {
+                declarator.init.is_some()
+                    || node_type(declarator).is_function()
+                    || defined_variables.insert(declarator.val.clone())
+            });
+        }
+    }
+}
+
+fn function_type(node: &Node) -> Type {
+    let param_tys = node
+        .params
+        .iter()
+        .map(|param| node_type(param).clone())
+        .collect();
+    let mut ty = Type::function_returning(node_type(node).clone(), param_tys);
+    ty.is_variadic = node.fixed_arg_count.is_some();
+    ty
+}
+
+fn node_type(node: &Node) -> &Type {
+    match &node.ty {
+        Some(ty) => ty,
+        _ => {
+            panic!("Type of {} is not determined", node.val);
+        }
+    }
+}
+
+fn enter_scope(context: &mut TypeContext) {
+    context.variable_maps.push(HashMap::new());
+    context.tag_maps.push(HashMap::new());
+    context.typedef_maps.push(HashMap::new());
+    context.constant_maps.push(HashMap::new());
+    context.static_maps.push(HashMap::new());
+}
+
+fn leave_scope(context: &mut TypeContext) {
+    context.variable_maps.pop();
+    context.tag_maps.pop();
+    context.typedef_maps.pop();
+    context.constant_maps.pop();
+    context.static_maps.pop();
+}
+
+fn find_tag(context: &TypeContext, tag: &str) -> Option<usize> {
+    context
+        .tag_maps
+        .iter()
+        .rev()
+        .find_map(|tag_map| tag_map.get(tag).copied())
+}
+
+// Replaces the typedef names, structs and unions in `ty` with their definitions, laying out any
+// new definition, and computes the lengths of arrays that are given as constant expressions.
+fn resolve_type(context: &mut TypeContext, ty: &Type) -> Type {
+    let mut resolved = match ty.kind {
+        TypeKind::TyPointer => Type::pointer_to(resolve_type(context, pointee_type(ty))),
+        TypeKind::TyFunction => match &ty.base {
+            Some(base) => {
+                // a parameter declared as an array or a function is a pointer
+                let param_tys = ty
+                    .param_tys
+                    .iter()
+                    .map(|param_ty| resolve_type(context, param_ty).decay())
+                    .collect();
+                let mut function_ty =
+                    Type::function_returning(resolve_type(context, base), param_tys);
+                function_ty.is_variadic = ty.is_variadic;
+                check_by_value(&function_ty);
+                function_ty
+            }
+            _ => {
+                panic!("Type error: function of unknown return type");
+            }
+        },
+        TypeKind::TyArray => match &ty.base {
+            Some(base) => {
+                let len = match &ty.array_len_expr {
+                    Some(node) => {
+                        let mut node = (**node).clone();
+                        check(&mut node, context);
+                        let len = intermediate_code_generator::eval(&node);
+                        if len < 0 {
+                            panic!("Type error: array size is negative");
+                        }
+                        len as usize
+                    }
+                    _ => ty.array_len,
+                };
+                Type::array_of(resolve_type(context, base), len)
+            }
+            _ => {
+                panic!("Type error: array of unknown type");
+            }
+        },
+        TypeKind::TyStruct | TypeKind::TyUnion => match ty.struct_id {
+            Some(struct_id) if !context.structs.contains_key(&struct_id) => {
+                define_struct(context, ty, struct_id)
+            }
+            _ => complete_type(context, ty),
+        },
+        TypeKind::TyEnum => {
+            if let Some(enum_id) = ty.struct_id {
+                if !context.structs.contains_key(&enum_id) {
+                    define_enum(context, ty, enum_id);
+                }
+            }
+            Type::new(TypeKind::TyInt)
+        }
+        TypeKind::TyTypedef => match context
+            .typedef_maps
+            .iter()
+            .rev()
+            .find_map(|typedef_map| typedef_map.get(&ty.tag))
+        {
+            // the struct named by a typedef may have been defined after it
+            Some(defined) => complete_type(context, &defined.clone()),
+            _ => {
+                panic!("Type error: unknown type name {}", ty.tag);
+            }
+        },
+        _ => ty.clone(),
+    };
+    // a typedef name may be declared const (e.g. `const T x`) as well as its type
+    resolved.is_const |= ty.is_const;
+    resolved
+}
+
+// Defines the enumerators of an enum as constants in the current scope.
+fn define_enum(context: &mut TypeContext, ty: &Type, enum_id: usize) {
+    context.structs.insert(enum_id, Type::new(TypeKind::TyInt));
+    let mut value: i64 = 0;
+    for enumerator in ty.enumerators.iter() {
+        if let Some(node) = &enumerator.value {
+            let mut node = node.clone();
+            check(&mut node, context);
+            value = intermediate_code_generator::eval(&node);
+        }
+        if let Some(constant_map) = context.constant_maps.last_mut() {
+            if constant_map.contains_key(&enumerator.name) {
+                panic!("Redefinition of {}", enumerator.name);
+            }
+            constant_map.insert(enumerator.name.clone(), value);
+        }
+        value += 1;
+    }
+}
+
+// Returns the definition of a struct or union type, which stays incomplete
+// if only its tag is known (e.g. a member pointing to the struct being defined).
+fn complete_type(context: &TypeContext, ty: &Type) -> Type {
+    if !ty.is_struct() {
+        return ty.clone();
+    }
+    let struct_id = match ty.struct_id {
+        Some(struct_id) => Some(struct_id),
+        _ => find_tag(context, &ty.tag),
+    };
+    match struct_id.and_then(|struct_id| context.structs.get(&struct_id)) {
+        Some(defined) => {
+            let mut defined = defined.clone();
+            defined.is_const = ty.is_const;
+            defined
+        }
+        _ => ty.clone(),
+    }
+}
+
+fn is_complete(ty: &Type) -> bool {
+    !ty.is_void() && (!ty.is_struct() || ty.size > 0)
+}
+
+// Each member is placed at the next offset aligned to its own alignment, while the members of
+// a union all start at 0; the size is then rounded up to the alignment of the whole type.
+fn define_struct(context: &mut TypeContext, ty: &Type, struct_id: usize) -> Type {
+    // the tag is visible in the member list, so that a member can point to the struct itself
+    if !ty.tag.is_empty() {
+        if let Some(tag_map) = context.tag_maps.last_mut() {
+            tag_map.insert(ty.tag.clone(), struct_id);
+        }
+    }
+    let mut defined = Type::new(ty.kind.clone());
+    defined.tag = ty.tag.clone();
+    defined.struct_id = Some(struct_id);
+    context.structs.insert(struct_id, defined.clone());
+
+    let mut end: usize = 0;
+    for member in ty.members.iter() {
+        let member_ty = resolve_type(context, &member.ty);
+        if !is_complete(&member_ty) {
+            panic!(
+                "Type error: member {} has incomplete type {}",
+                member.name, member_ty
+            );
+        }
+        if defined
+            .members
+            .iter()
+            .any(|member_| member_.name == member.name)
+        {
+            panic!("Duplicate member {} in {}", member.name, defined);
+        }
+        let offset = match ty.kind {
+            TypeKind::TyUnion => 0,
+            _ => end.div_ceil(member_ty.align) * member_ty.align,
+        };
+        end = end.max(offset + member_ty.size);
+        defined.align = defined.align.max(member_ty.align);
+        defined.members.push(Member {
+            name: member.name.clone(),
+            ty: member_ty,
+            offset,
+        });
+    }
+    defined.size = end.div_ceil(defined.align) * defined.align;
+    context.structs.insert(struct_id, defined.clone());
+    defined
+}
+
+// Resolves the return and parameter types of a function in the current scope.
+fn resolve_signature(node: &mut Node, context: &mut TypeContext) {
+    let return_ty = resolve_type(context, node_type(node));
+    node.ty = Some(return_ty);
+    for param in node.params.iter_mut() {
+        // a parameter declared as an array through a typedef is a pointer as well
+        let ty = resolve_type(context, node_type(param)).decay();
+        param.ty = Some(ty);
+    }
+    let function_ty = function_type(node);
+    check_by_value(&function_ty);
+    context.functions.insert(node.val.clone(), function_ty);
+}
+
+// Structs and unions are not passed to or returned from functions by value,
+// since their values are only the addresses of the objects.
+fn check_by_value(function_ty: &Type) {
+    if let Some(return_ty) = &function_ty.base {
+        if return_ty.is_struct() {
+            panic!("Type error: cannot return {} by value", return_ty);
+        }
+    }
+    for param_ty in function_ty.param_tys.iter() {
+        check_argument(param_ty);
+    }
+}
+
+fn check_argument(ty: &Type) {
+    if ty.is_struct() {
+        panic!("Type error: cannot pass {} by value", ty);
+    }
+}
+
+fn declare(context: &mut TypeContext, name: &str, ty: Type) {
+    if let Some(variable_map) = context.variable_maps.last_mut() {
+        if variable_map.contains_key(name) {
+            panic!("Redefinition of {}", name);
+        }
+        variable_map.insert(String::from(name), ty);
+    }
+}
+
+// Returns the value of an enumerator unless a variable of the same name hides it.
+fn find_constant(context: &TypeContext, name: &str) -> Option<i64> {
+    for (variable_map, constant_map) in context
+        .variable_maps
+        .iter()
+        .zip(context.constant_maps.iter())
+        .rev()
+    {
+        if variable_map.contains_key(name) {
+            return None;
+        }
+        if let Some(value) = constant_map.get(name) {
+            return Some(*value);
+        }
+    }
+    None
+}
+
+// Returns the label of a static local variable unless another variable of the same name hides it.
+fn find_static_label(context: &TypeContext, name: &str) -> Option<String> {
+    context
+        .variable_maps
+        .iter()
+        .zip(context.static_maps.iter())
+        .rev()
+        .find(|(variable_map, _)| variable_map.contains_key(name))
+        .and_then(|(_, static_map)| static_map.get(name).cloned())
+}
+
+fn find_variable(context: &TypeContext, name: &str) -> Option<Type> {
+    context
+        .variable_maps
+        .iter()
+        .rev()
+        .find_map(|variable_map| variable_map.get(name).cloned())
+}
+
+// Checks that the value of `node` can be implicitly converted to `ty`,
+// and wraps it in a cast if the types differ.
+fn convert(node: Node, ty: &Type, context_name: &str) -> Node {
+    // an array is converted as a pointer to its first element
+    let from = &node_type(&node).decay();
+    // a pointer may gain const on its pointee, but not lose it
+    if ty.is_pointer()
+        && from.is_pointer()
+        && pointee_type(from).is_const
+        && !pointee_type(ty).is_const
+    {
+        panic!(
+            "Type error: conversion from {} to {} in {} discards const",
+            from, ty, context_name
+        );
+    }
+    if from == ty {
+        return node;
+    }
+    // the integer constant 0 is a null pointer,
+    // and a pointer to void is converted to and from any other pointer
+    let is_null_pointer = matches!(node.kind, NodeKind::NdNum) && node.val == "0";
+    let is_void_pointer = ty.is_pointer()
+        && from.is_pointer()
+        && (pointee_type(ty).is_void() || pointee_type(from).is_void());
+    if !(ty.is_arithmetic() && from.is_arithmetic()
+        || ty.is_pointer() && is_null_pointer
+        || is_void_pointer)
+    {
+        panic!(
+            "Type error: cannot convert {} to {} in {}",
+            from, ty, context_name
+        );
+    }
+    let mut cast = Node::new("cast", NodeKind::NdCast);
+    cast.left = Some(Box::new(node));
+    cast.ty = Some(ty.clone());
+    cast
+}
+
+fn convert_child(child: &mut Option<Box<Node>>, ty: &Type, context_name: &str) {
+    if let Some(node) = child.take() {
+        *child = Some(Box::new(convert(*node, ty, context_name)));
+    }
+}
+
+// Compares a floating-point condition with 0, so that only integers are tested by jumps.
+fn convert_condition(child: &mut Option<Box<Node>>) {
+    if let Some(node) = child.take() {
+        let ty = node_type(&node).clone();
+        if !ty.is_floating() {
+            *child = Some(node);
+            return;
+        }
+        let mut zero = Node::new("0", NodeKind::NdNum);
+        zero.ty = Some(ty);
+        let mut comparison = new_binary("!=", NodeKind::NdComparisonOperator, *node, zero);
+        comparison.ty = Some(Type::new(TypeKind::TyInt));
+        *child = Some(Box::new(comparison));
+    }
+}
+
+// Multiplies the integer operand of pointer arithmetic by the size of the pointee,
+// so that `p + n` points `n` elements ahead of `p`.
+fn scale_child(child: &mut Option<Box<Node>>, pointer_ty: &Type) {
+    convert_child(child, &Type::new(TypeKind::TyLong), "pointer arithmetic");
+    let mut size = Node::new(&pointee_type(pointer_ty).size.to_string(), NodeKind::NdNum);
+    size.ty = Some(Type::new(TypeKind::TyLong));
+    let mut mul = Node::new("*", NodeKind::NdOperator);
+    mul.left = child.take();
+    mul.right = Some(Box::new(size));
+    mul.ty = Some(Type::new(TypeKind::TyLong));
+    *child = Some(Box::new(mul));
+}
+
+fn pointee_type(ty: &Type) -> &Type {
+    match &ty.base {
+        Some(base) if ty.is_pointer() => base,
+        _ => {
+            panic!("Type error: {} is not a pointer", ty);
+        }
+    }
+}
+
+fn operand_types(node: &Node) -> (Type, Type) {
+    match (&node.left, &node.right) {
+        (Some(node_left), Some(node_right)) => {
+            (node_type(node_left).decay(), node_type(node_right).decay())
+        }
+        _ => {
+            panic!("Expected operands of {}", node.val);
+        }
+    }
+}
+
+// char (signed or unsigned) is computed as int
+fn promoted_type(ty: &Type) -> Type {
+    match ty.kind {
+        TypeKind::TyChar | TypeKind::TyEnum => Type::new(TypeKind::TyInt),
+        _ => ty.clone(),
+    }
+}
+
+// the usual arithmetic conversions: operands are computed as a floating-point type if either
+// of them is one (double rather than float), otherwise as int unless one is long,
+// and as unsigned if an unsigned operand is at least as wide as the other one
+fn arithmetic_type(left_ty: &Type, right_ty: &Type) -> Type {
+    if left_ty.is_floating() || right_ty.is_floating() {
+        let is_wider = !right_ty.is_floating() || left_ty.size > right_ty.size;
+        return if left_ty.is_floating() && is_wider {
+            left_ty.clone()
+        } else {
+            right_ty.clone()
+        };
+    }
+    let (left_ty, right_ty) = (promoted_type(left_ty), promoted_type(right_ty));
+    if left_ty.size != right_ty.size {
+        return if left_ty.size > right_ty.size {
+            left_ty
+        } else {
+            right_ty
+        };
+    }
+    let mut ty = left_ty;
+    ty.is_unsigned = ty.is_unsigned || right_ty.is_unsigned;
+    ty
+}
+
+fn is_lvalue(node: &Node) -> bool {
+    matches!(
+        node.kind,
+        NodeKind::NdVariable | NodeKind::NdDereference | NodeKind::NdMember
+    )
+}
+
+fn new_binary(val: &str, kind: NodeKind, left: Node, right: Node) -> Node {
+    let mut node = Node::new(val, kind);
+    node.left = Some(Box::new(left));
+    node.right = Some(Box::new(right));
+    node
+}
+
+// Lowers `a op= b` to `a = a op b`. Unless `a` is a plain variable, its address is
+// evaluated only once through a hidden pointer: `(tmp = &a, *tmp = *tmp op b)`.
+fn lower_compound_assignment(node: &mut Node, context: &mut TypeContext) {
+    let op = &node.val[..node.val.len() - 1];
+    let (node_left, node_right) = match (node.left.take(), node.right.take()) {
+        (Some(node_left), Some(node_right)) if is_lvalue(&node_left) => (*node_left, *node_right),
+        _ => {
+            panic!("Expected lvalue to the left of {}", node.val);
+        }
+    };
+    if matches!(node_left.kind, NodeKind::NdVariable) {
+        let variable = Node::new(&node_left.val, NodeKind::NdVariable);
+        let operation = new_binary(op, NodeKind::NdOperator, variable, node_right);
+        *node = new_binary("=", NodeKind::NdAssignOperator, node_left, operation);
+        check(node, context);
+        return;
+    }
+
+    let (store, name) = hold_address(node_left, context);
+    let operation = new_binary(op, NodeKind::NdOperator, dereference(&name), node_right);
+    let mut assign = new_binary(
+        "=",
+        NodeKind::NdAssignOperator,
+        dereference(&name),
+        operation,
+    );
+    check(&mut assign, context);
+    let ty = node_type(&assign).clone();
+    *node = new_binary(",", NodeKind::NdComma, store, assign);
+    node.ty = Some(ty);
+}
+
+// Lowers `a++` to `(old = a, a = old + 1, old)`, keeping the old value in a hidden variable
+// because computing it back from the new one is not exact for floating types.
+// Unless `a` is a plain variable, its address is evaluated only once as in `a op= b`.
+fn lower_postfix_operator(node: &mut Node, context: &mut TypeContext) {
+    let op = if node.val == "++" { "+" } else { "-" };
+    let node_left = match node.left.take() {
+        Some(node_left) if is_lvalue(&node_left) => *node_left,
+        _ => {
+            panic!("Expected lvalue to the left of {}", node.val);
+        }
+    };
+    let (store, target) = if matches!(node_left.kind, NodeKind::NdVariable) {
+        (None, node_left)
+    } else {
+        let (store, name) = hold_address(node_left, context);
+        (Some(store), dereference(&name))
+    };
+    let mut value = target.clone();
+    check(&mut value, context);
+    let mut ty = node_type(&value).clone();
+    if !ty.is_arithmetic() && !ty.is_pointer() {
+        panic!("Type error: invalid operand of {} ({})", node.val, ty);
+    }
+    ty.is_const = false;
+    let old = new_temporary(context, ty.clone());
+    let variable = || Node::new(&old, NodeKind::NdVariable);
+
+    let load = new_binary("=", NodeKind::NdAssignOperator, variable(), target.clone());
+    let operation = new_binary(
+        op,
+        NodeKind::NdOperator,
+        variable(),
+        Node::new("1", NodeKind::NdNum),
+    );
+    let assign = new_binary("=", NodeKind::NdAssignOperator, target, operation);
+    let result = new_binary(",", NodeKind::NdComma, assign, variable());
+    let mut lowered = new_binary(",", NodeKind::NdComma, load, result);
+    check(&mut lowered, context);
+    *node = match store {
+        Some(store) => new_binary(",", NodeKind::NdComma, store, lowered),
+        _ => lowered,
+    };
+    node.ty = Some(ty);
+}
+
+// Evaluates the address of the lvalue `a` into a hidden pointer `tmp`, through which `*tmp`
+// designates `a`. returns `tmp = &a`, which is already checked, and the name of `tmp`
+fn hold_address(node_left: Node, context: &mut TypeContext) -> (Node, String) {
+    let mut address = Node::new("&", NodeKind::NdAddress);
+    address.left = Some(Box::new(node_left));
+    check(&mut address, context);
+    let pointer_ty = node_type(&address).clone();
+    let name = new_temporary(context, pointer_ty.clone());
+    let mut temporary = Node::new(&name, NodeKind::NdVariable);
+    temporary.ty = Some(pointer_ty.clone());
+    let mut store = new_binary("=", NodeKind::NdAssignOperator, temporary, address);
+    store.ty = Some(pointer_ty);
+    (store, name)
+}
+
+// declares a hidden variable, whose name cannot collide with an identifier in the source
+fn new_temporary(context: &mut TypeContext, ty: Type) -> String {
+    let name = format!(".tmp.{}", context.temporary_count);
+    context.temporary_count += 1;
+    declare(context, &name, ty);
+    name
+}
+
+fn dereference(name: &str) -> Node {
+    let mut node = Node::new("*", NodeKind::NdDereference);
+    node.left = Some(Box::new(Node::new(name, NodeKind::NdVariable)));
+    node
+}
+
+fn element_type(ty: &Type) -> &Type {
+    match &ty.base {
+        Some(base) if ty.is_array() => base,
+        _ => {
+            panic!("Type error: {} is not an array", ty);
+        }
+    }
+}
+
+fn is_char_array(ty: &Type) -> bool {
+    ty.is_array() && matches!(element_type(ty).kind, TypeKind::TyChar)
+}
+
+// An initializer is flattened into the values it gives to the scalars (or structs) of a variable,
+// each kept in a designator with its offset in the variable, and the rest of the variable is zero.
+// returns the number of elements of an array, which is the length of `int a[] = {1, 2}`
+fn initialize(
+    context: &mut TypeContext,
+    ty: &Type,
+    node: &Node,
+    offset: usize,
+    values: &mut Vec<Node>,
+) -> usize {
+    match node.kind {
+        NodeKind::NdInitializer if ty.is_array() || ty.is_struct() => {
+            let mut pos = 0;
+            let len = initialize_elements(context, ty, &node.body, &mut pos, offset, values, true);
+            if pos < node.body.len() {
+                panic!("Type error: excess elements in initializer of {}", ty);
+            }
+            len
+        }
+        NodeKind::NdInitializer => match &node.body[..] {
+            // a scalar may be enclosed in braces
+            [element] if !matches!(element.kind, NodeKind::NdDesignator) => {
+                initialize(context, ty, element, offset, values)
+            }
+            _ => {
+                panic!("Type error: invalid initializer of {}", ty);
+            }
+        },
+        NodeKind::NdString if is_char_array(ty) => {
+            // the terminating null character is left out if the array has no room for it
+            let mut bytes = lexer::decode_escapes(&node.val);
+            bytes.push(0);
+            let len = if ty.array_len == 0 {
+                bytes.len()
+            } else {
+                ty.array_len
+            };
+            if bytes.len() > len + 1 {
+                panic!("Type error: initializer string is too long for {}", ty);
+            }
+            let base = element_type(ty);
+            for (i, byte) in bytes.iter().take(len).enumerate() {
+                let val = if base.is_unsigned {
+                    byte.to_string()
+                } else {
+                    (*byte as i8).to_string()
+                };
+                let mut value = Node::new(&val, NodeKind::NdNum);
+                value.ty = Some(base.clone());
+                push_value(values, offset + i, base, value);
+            }
+            len
+        }
+        _ if ty.is_array() => {
+            panic!(
+                "Type error: array {} must be initialized by an initializer list",
+                ty
+            );
+        }
+        _ => {
+            let mut value = node.clone();
+            check(&mut value, context);
+            push_value(values, offset, ty, convert(value, ty, "initialization"));
+            0
+        }
+    }
+}
+
+// Initializes the elements (or members) of ty from elements[*pos..], where an element that is
+// not enclosed in braces may begin a nested array or struct (e.g. `int a[2][2] = {1, 2, 3, 4}`).
+// only a list in braces may designate its elements, and the elements after a designated one
+// follow it. returns the number of elements of an array as initialize does
+fn initialize_elements(
+    context: &mut TypeContext,
+    ty: &Type,
+    elements: &[Node],
+    pos: &mut usize,
+    offset: usize,
+    values: &mut Vec<Node>,
+    is_braced: bool,
+) -> usize {
+    let mut i = 0;
+    let mut len = 0;
+    while let Some(element) = elements.get(*pos) {
+        match (&element.kind, &element.left) {
+            (NodeKind::NdDesignator, Some(value)) if is_braced => {
+                i = designated_index(context, ty, element);
+                let (element_ty, element_offset) = element_of(ty, i);
+                let value = std::slice::from_ref(&**value);
+                if matches!(value[0].kind, NodeKind::NdDesignator) {
+                    initialize_elements(
+                        context,
+                        &element_ty,
+                        value,
+                        &mut 0,
+                        offset + element_offset,
+                        values,
+                        true,
+                    );
+                } else {
+                    initialize_element(
+                        context,
+                        &element_ty,
+                        value,
+                        &mut 0,
+                        offset + element_offset,
+                        values,
+                    );
+                }
+                *pos += 1;
+            }
+            (NodeKind::NdDesignator, _) => {
+                break;
+            }
+            _ => {
+                let is_full = if ty.is_array() {
+                    ty.array_len != 0 && i >= ty.array_len
+                } else {
+                    // only the first member of a union is initialized without a designator
+                    i >= ty.members.len() || matches!(ty.kind, TypeKind::TyUnion) && i > 0
+                };
+                if is_full {
+                    break;
+                }
+                let (element_ty, element_offset) = element_of(ty, i);
+                initialize_element(
+                    context,
+                    &element_ty,
+                    elements,
+                    pos,
+                    offset + element_offset,
+                    values,
+                );
+            }
+        }
+        i += 1;
+        len = len.max(i);
+    }
+    len
+}
+
+// Initializes an object of type ty from elements[*pos], or from as many elements as it takes
+// if it is an array or a struct that is not given by braces, a string or a struct value.
+fn initialize_element(
+    context: &mut TypeContext,
+    ty: &Type,
+    elements: &[Node],
+    pos: &mut usize,
+    offset: usize,
+    values: &mut Vec<Node>,
+) {
+    let element = &elements[*pos];
+    let is_elided = (ty.is_array() || ty.is_struct())
+        && !matches!(element.kind, NodeKind::NdInitializer)
+        && !(is_char_array(ty) && matches!(element.kind, NodeKind::NdString));
+    if is_elided {
+        if ty.is_struct() {
+            let mut value = element.clone();
+            check(&mut value, context);
+            if node_type(&value) == ty {
+                push_value(values, offset, ty, value);
+                *pos += 1;
+                return;
+            }
+        }
+        initialize_elements(context, ty, elements, pos, offset, values, false);
+    } else {
+        initialize(context, ty, element, offset, values);
+        *pos += 1;
+    }
+}
+
+// Returns the index of the element or the member chosen by a designator.
+fn designated_index(context: &mut TypeContext, ty: &Type, designator: &Node) -> usize {
+    match &designator.cond {
+        Some(index) if ty.is_array() => {
+            let mut index = (**index).clone();
+            check(&mut index, context);
+            let index = intermediate_code_generator::eval(&index);
+            if index < 0 || ty.array_len != 0 && index as usize >= ty.array_len {
+                panic!(
+                    "Type error: array index {} is out of bounds in initializer of {}",
+                    index, ty
+                );
+            }
+            index as usize
+        }
+        None if ty.is_struct() => {
+            match ty
+                .members
+                .iter()
+                .position(|member| member.name == designator.val)
+            {
+                Some(i) => i,
+                _ => {
+                    panic!("Type error: {} has no member named {}", ty, designator.val);
+                }
+            }
+        }
+        _ => {
+            panic!("Type error: designator does not match {}", ty);
+        }
+    }
+}
+
+// Returns the type and the offset of the element or the member i.
+fn element_of(ty: &Type, i: usize) -> (Type, usize) {
+    if ty.is_array() {
+        let base = element_type(ty);
+        (base.clone(), i * base.size)
+    } else {
+        let member = &ty.members[i];
+        (member.ty.clone(), member.offset)
+    }
+}
+
+// A later initializer of the same object overrides the earlier ones.
+// The designator has the type of the object, which an array or a function value decays to.
+fn push_value(values: &mut Vec<Node>, offset: usize, ty: &Type, value: Node) {
+    let size = ty.size;
+    values.retain(|designator| {
+        designator.offset + node_type(designator).size <= offset
+            || offset + size <= designator.offset
+    });
+    let mut designator = Node::new("", NodeKind::NdDesignator);
+    designator.offset = offset;
+    designator.ty = Some(ty.clone());
+    designator.left = Some(Box::new(value));
+    values.push(designator);
+}
+
+fn check_child(child: &mut Option<Box<Node>>, context: &mut TypeContext) {
+    if let Some(node) = child {
+        check(node, context);
+    }
+}
+
+fn check(node: &mut Node, context: &mut TypeContext) {
+    match node.kind {
+        NodeKind::NdFunction => {
+            resolve_signature(node, context);
+            context.return_ty = node_type(node).clone();
+            context.is_variadic = node.fixed_arg_count.is_some();
+            enter_scope(context);
+            for param in node.params.iter() {
+                declare(context, &param.val, node_type(param).clone());
+            }
+            for node_ in node.body.iter_mut() {
+                check(node_, context);
+            }
+            leave_scope(context);
+        }
+        NodeKind::NdFunctionDeclaration => {
+            resolve_signature(node, context);
+        }
+        NodeKind::NdBlock => {
+            enter_scope(context);
+            for node_ in node.body.iter_mut() {
+                check(node_, context);
+            }
+            leave_scope(context);
+        }
+        NodeKind::NdTypedef => {
+            let base = resolve_type(context, node_type(node));
+            node.ty = Some(base);
+            for declarator in node.body.iter_mut() {
+                let ty = resolve_type(context, node_type(declarator));
+                declarator.ty = Some(ty.clone());
+                if let Some(typedef_map) = context.typedef_maps.last_mut() {
+                    typedef_map.insert(declarator.val.clone(), ty);
+                }
+            }
+        }
+        NodeKind::NdDeclaration => {
+            // the base type is resolved first so that a struct defined in it is defined only once
+            let base = resolve_type(context, node_type(node));
+            node.ty = Some(base);
+            let is_global = context.variable_maps.len() == 1;
+            let is_static = node.storage_class == StorageClass::ScStatic;
+            for declarator in node.body.iter_mut() {
+                let ty = resolve_type(context, node_type(declarator));
+                if !is_complete(&ty) {
+                    panic!(
+                        "Type error: variable {} has incomplete type {}",
+                        declarator.val, ty
+                    );
+                }
+                declarator.ty = Some(ty.clone());
+                if ty.is_function() {
+                    // a function declared in a block is defined elsewhere
+                    context.functions.insert(declarator.val.clone(), ty);
+                    continue;
+                }
+                // a global variable may be declared any number of times with the same type,
+                // before or after its definition, but initialized only once
+                if is_global && find_variable(context, &declarator.val).is_some() {
+                    if find_variable(context, &declarator.val) != Some(ty.clone()) {
+                        panic!("Type error: conflicting types for {}", declarator.val);
+                    }
+                } else {
+                    declare(context, &declarator.val, ty.clone());
+                }
+                if is_global
+                    && declarator.init.is_some()
+                    && !context.initialized_variables.insert(declarator.val.clone())
+                {
+                    panic!("Redefinition of {}", declarator.val);
+                }
+                // a global or static variable is defined by its initial data, which is flattened
+                // as well as an initializer list, the string that initializes an array and
+                // the value of a const variable, which cannot be assigned
+                match declarator.init.take() {
+                    Some(init)
+                        if is_global
+                            || is_static
+                            || ty.is_array()
+                            || ty.is_const
+                            || matches!(init.kind, NodeKind::NdInitializer) =>
+                    {
+                        // the parser has lowered `= value` to the statement `name = value;`
+                        let init = match init.kind {
+                            NodeKind::NdInitializer => *init,
+                            _ => match init.left.and_then(|assignment| assignment.right) {
+                                Some(value) => *value,
+                                _ => {
+                                    panic!("Expected initializer of {}", declarator.val);
+                                }
+                            },
+                        };
+                        let mut values = Vec::new();
+                        let len = initialize(context, &ty, &init, 0, &mut values);
+                        values.sort_by_key(|value| value.offset);
+                        if ty.is_array() && ty.array_len == 0 {
+                            let ty = Type::array_of(element_type(&ty).clone(), len);
+                            if let Some(variable_map) = context.variable_maps.last_mut() {
+                                variable_map.insert(declarator.val.clone(), ty.clone());
+                            }
+                            declarator.ty = Some(ty);
+                        }
+                        let mut node_init = Node::new("initializer", NodeKind::NdInitializer);
+                        node_init.body = values;
+                        declarator.init = Some(Box::new(node_init));
+                    }
+                    init => {
+                        declarator.init = init;
+                        check_child(&mut declarator.init, context);
+                    }
+                }
+                if is_static && !is_global {
+                    // a static local variable is a global one under a label of its own,
+                    // by which it is referred to
+                    let label = format!(".L.static.{}.{}", declarator.val, context.temporary_count);
+                    context.temporary_count += 1;
+                    if let Some(static_map) = context.static_maps.last_mut() {
+                        static_map.insert(declarator.val.clone(), label.clone());
+                    }
+                    declare(context, &label, node_type(declarator).clone());
+                    declarator.val = label;
+                }
+            }
+        }
+        NodeKind::NdIf | NodeKind::NdWhile | NodeKind::NdFor | NodeKind::NdDoWhile => {
+            // a variable declared in the initialization of for is visible only inside the loop
+            let has_scope = matches!(&node.init, Some(node_init) if matches!(node_init.kind, NodeKind::NdDeclaration));
+            if has_scope {
+                enter_scope(context);
+            }
+            check_child(&mut node.init, context);
+            check_child(&mut node.cond, context);
+            convert_condition(&mut node.cond);
+            check_child(&mut node.inc, context);
+            check_child(&mut node.then, context);
+            check_child(&mut node.els, context);
+            if has_scope {
+                leave_scope(context);
+            }
+        }
+        NodeKind::NdSwitch => {
+            check_child(&mut node.cond, context);
+            let ty = match &node.cond {
+                Some(node_cond) if node_type(node_cond).is_integer() => {
+                    promoted_type(node_type(node_cond))
+                }
+                _ => {
+                    panic!("Type error: switch quantity is not an integer");
+                }
+            };
+            convert_child(&mut node.cond, &ty, "switch");
+            check_child(&mut node.then, context);
+        }
+        NodeKind::NdCase => {
+            check_child(&mut node.left, context);
+            if let Some(node_left) = &node.left {
+                if !node_type(node_left).is_integer() {
+                    panic!("Type error: case label is not an integer");
+                }
+            }
+            check_child(&mut node.then, context);
+        }
+        NodeKind::NdReturn => {
+            check_child(&mut node.left, context);
+            let return_ty = context.return_ty.clone();
+            // only a function returning void returns without a value
+            match (&node.left, return_ty.is_void()) {
+                (Some(_), true) => {
+                    panic!("Type error: return with a value in a function returning void");
+                }
+                (None, false) => {
+                    panic!(
+                        "Type error: return without a value in a function returning {}",
+                        return_ty
+                    );
+                }
+                _ => {}
+            }
+            convert_child(&mut node.left, &return_ty, "return");
+        }
+        NodeKind::NdCast => {
+            // the conversions inserted by the type checker are never checked again,
+            // so this is an explicit cast between scalar types or to void
+            check_child(&mut node.left, context);
+            let ty = resolve_type(context, node_type(node));
+            let from = match &node.left {
+                Some(node_left) => node_type(node_left).decay(),
+                _ => {
+                    panic!("Expected operand of cast");
+                }
+            };
+            let is_scalar = |ty: &Type| ty.is_arithmetic() || ty.is_pointer();
+            // a pointer cannot be converted to or from a floating-point number,
+            // while any value can be discarded by a cast to void
+            let is_pointer_and_floating =
+                |ty: &Type, from: &Type| ty.is_pointer() && from.is_floating();
+            if !ty.is_void()
+                && (!is_scalar(&ty)
+                    || !is_scalar(&from)
+                    || is_pointer_and_floating(&ty, &from)
+                    || is_pointer_and_floating(&from, &ty))
+            {
+                panic!("Type error: cannot cast {} to {}", from, ty);
+            }
+            node.ty = Some(ty);
+        }
+        NodeKind::NdExpressionStatement => {
+            check_child(&mut node.left, context);
+        }
+        NodeKind::NdBreak | NodeKind::NdContinue | NodeKind::NdGoto => {}
+        NodeKind::NdInitializer | NodeKind::NdDesignator => {
+            // Do nothing because it is flattened with its declaration.
+        }
+        NodeKind::NdLabel => {
+            check_child(&mut node.then, context);
+        }
+        NodeKind::NdNum if matches!(&node.ty, Some(ty) if ty.is_floating()) => {
+            // the type of a floating-point literal is given by the parser
+        }
+        NodeKind::NdNum => {
+            // a literal is int unless its value or suffix needs a wider or unsigned type
+            let is_unsigned = matches!(&node.ty, Some(ty) if ty.is_unsigned);
+            let is_long = matches!(&node.ty, Some(ty) if matches!(ty.kind, TypeKind::TyLong));
+            let fits_int = if is_unsigned {
+                node.val.parse::<u32>().is_ok()
+            } else {
+                node.val.parse::<i32>().is_ok()
+            };
+            let mut ty = if fits_int && !is_long {
+                Type::new(TypeKind::TyInt)
+            } else {
+                Type::new(TypeKind::TyLong)
+            };
+            ty.is_unsigned = is_unsigned;
+            node.ty = Some(ty);
+        }
+        NodeKind::NdString => {
+            // a string literal is an array of its bytes followed by the terminating null character
+            let len = lexer::decode_escapes(&node.val).len() + 1;
+            node.ty = Some(Type::array_of(Type::new(TypeKind::TyChar), len));
+        }
+        NodeKind::NdVaStart => {
+            if !context.is_variadic {
+                panic!("va_start used in a function with fixed arguments");
+            }
+            node.ty = Some(Type::pointer_to(Type::new(TypeKind::TyChar)));
+        }
+        NodeKind::NdVariable if find_constant(context, &node.val).is_some() => {
+            // an enumerator is an integer constant
+            if let Some(value) = find_constant(context, &node.val) {
+                *node = Node::new(&value.to_string(), NodeKind::NdNum);
+                check(node, context);
+            }
+        }
+        NodeKind::NdVariable => match find_variable(context, &node.val) {
+            Some(ty) => {
+                if let Some(label) = find_static_label(context, &node.val) {
+                    node.val = label;
+                }
+                node.ty = Some(ty);
+            }
+            // the name of a function designates the function, which is used as its address
+            _ if context.functions.contains_key(&node.val) => {
+                if let Some(function_ty) = context.functions.get(&node.val).cloned() {
+                    node.ty = Some(resolve_type(context, &function_ty));
+                }
+            }
+            _ => {
+                panic!("Type error: undeclared variable {}", node.val);
+            }
+        },
+        NodeKind::NdAssignOperator if node.val != "=" => {
+            lower_compound_assignment(node, context);
+        }
+        NodeKind::NdPostfixOperator => {
+            lower_postfix_operator(node, context);
+        }
+        NodeKind::NdConditional => {
+            check_child(&mut node.cond, context);
+            check_child(&mut node.then, context);
+            check_child(&mut node.els, context);
+            let (cond_ty, then_ty, els_ty) = match (&node.cond, &node.then, &node.els) {
+                (Some(node_cond), Some(node_then), Some(node_els)) => (
+                    node_type(node_cond).decay(),
+                    node_type(node_then).decay(),
+                    node_type(node_els).decay(),
+                ),
+                _ => {
+                    panic!("Expected operands of ?:");
+                }
+            };
+            if !cond_ty.is_arithmetic() && !cond_ty.is_pointer() {
+                panic!("Type error: invalid condition of ?: ({})", cond_ty);
+            }
+            convert_condition(&mut node.cond);
+            // both arms are converted to a common type, which points to const if either does
+            let is_els_const = els_ty.is_pointer() && pointee_type(&els_ty).is_const;
+            let ty = if then_ty.is_arithmetic() && els_ty.is_arithmetic() {
+                arithmetic_type(&then_ty, &els_ty)
+            } else if then_ty.is_pointer() && !is_els_const {
+                then_ty
+            } else if els_ty.is_pointer() {
+                els_ty
+            } else if then_ty == els_ty {
+                then_ty
+            } else {
+                panic!(
+                    "Type error: mismatched operands of ?: ({} and {})",
+                    then_ty, els_ty
+                );
+            };
+            if !ty.is_struct() {
+                convert_child(&mut node.then, &ty, "conditional expression");
+                convert_child(&mut node.els, &ty, "conditional expression");
+            }
+            node.ty = Some(ty);
+        }
+        NodeKind::NdComma => {
+            check_child(&mut node.left, context);
+            check_child(&mut node.right, context);
+            if let Some(node_right) = &node.right {
+                node.ty = Some(node_type(node_right).clone());
+            }
+        }
+        NodeKind::NdAssignOperator => {
+            check_child(&mut node.right, context);
+            let ty = match &mut node.left {
+                Some(node_left)
+                    if matches!(node_left.kind, NodeKind::NdVariable)
+                        && find_constant(context, &node_left.val).is_none() =>
+                {
+                    // assigning to an undeclared variable declares it as long in the current scope
+                    let ty = match find_variable(context, &node_left.val) {
+                        Some(ty) => ty,
+                        _ => {
+                            let ty = Type::new(TypeKind::TyLong);
+                            declare(context, &node_left.val, ty.clone());
+                            ty
+                        }
+                    };
+                    if let Some(label) = find_static_label(context, &node_left.val) {
+                        node_left.val = label;
+                    }
+                    node_left.ty = Some(ty.clone());
+                    ty
+                }
+                Some(node_left)
+                    if is_lvalue(node_left) && !matches!(node_left.kind, NodeKind::NdVariable) =>
+                {
+                    check(node_left, context);
+                    node_type(node_left).clone()
+                }
+                _ => {
+                    panic!("Expected lvalue to the left of =");
+                }
+            };
+            if ty.is_array() {
+                panic!("Type error: cannot assign to an array of type {}", ty);
+            }
+            if ty.is_const {
+                panic!(
+                    "Type error: cannot assign to a read-only object of type {}",
+                    ty
+                );
+            }
+            convert_child(&mut node.right, &ty, "assignment");
+            node.ty = Some(ty);
+        }
+        NodeKind::NdOperator => {
+            check_child(&mut node.left, context);
+            check_child(&mut node.right, context);
+            let (left_ty, right_ty) = operand_types(node);
+            match (&node.val[..], left_ty.is_pointer(), right_ty.is_pointer()) {
+                ("<<" | ">>", false, false) if left_ty.is_integer() && right_ty.is_integer() => {
+                    // the result of a shift has the type of its left operand
+                    node.ty = Some(promoted_type(&left_ty));
+                }
+                (_, false, false) if left_ty.is_integer() && right_ty.is_integer() => {
+                    node.ty = Some(arithmetic_type(&left_ty, &right_ty));
+                }
+                ("+" | "-" | "*" | "/", false, false)
+                    if left_ty.is_arithmetic() && right_ty.is_arithmetic() =>
+                {
+                    // a floating-point operation needs both operands in the same format
+                    let ty = arithmetic_type(&left_ty, &right_ty);
+                    convert_child(&mut node.left, &ty, "arithmetic");
+                    convert_child(&mut node.right, &ty, "arithmetic");
+                    node.ty = Some(ty);
+                }
+                ("+" | "-", true, false) if right_ty.is_integer() => {
+                    scale_child(&mut node.right, &left_ty);
+                    node.ty = Some(left_ty);
+                }
+                ("+", false, true) if left_ty.is_integer() => {
+                    scale_child(&mut node.left, &right_ty);
+                    node.ty = Some(right_ty);
+                }
+                ("-", true, true) if left_ty == right_ty => {
+                    // the difference of two pointers is the number of elements between them
+                    let mut size =
+                        Node::new(&pointee_type(&left_ty).size.to_string(), NodeKind::NdNum);
+                    size.ty = Some(Type::new(TypeKind::TyLong));
+                    node.ty = Some(Type::new(TypeKind::TyLong));
+                    let sub = std::mem::replace(node, Node::new("/", NodeKind::NdOperator));
+                    node.left = Some(Box::new(sub));
+                    node.right = Some(Box::new(size));
+                    node.ty = Some(Type::new(TypeKind::TyLong));
+                }
+                _ => {
+                    panic!(
+                        "Type error: invalid operands to {} ({} and {})",
+                        node.val, left_ty, right_ty
+                    );
+                }
+            }
+        }
+        NodeKind::NdComparisonOperator => {
+            check_child(&mut node.left, context);
+            check_child(&mut node.right, context);
+            let (left_ty, right_ty) = operand_types(node);
+            // a pointer can be compared with a pointer of the same type or the null pointer,
+            // whatever const its pointee has, and a pointer to void with any pointer
+            if left_ty.is_pointer() && right_ty.is_pointer() {
+                if pointee_type(&left_ty).is_void() {
+                    convert_child(&mut node.left, &right_ty, "comparison");
+                } else if left_ty != right_ty {
+                    convert_child(&mut node.right, &left_ty, "comparison");
+                }
+            } else if left_ty.is_pointer() {
+                convert_child(&mut node.right, &left_ty, "comparison");
+            } else if right_ty.is_pointer() {
+                convert_child(&mut node.left, &right_ty, "comparison");
+            } else if left_ty.is_arithmetic() && right_ty.is_arithmetic() {
+                // the operands are compared as their common type, so that a negative int
+                // is compared with an unsigned int as a large unsigned value
+                let ty = arithmetic_type(&left_ty, &right_ty);
+                convert_child(&mut node.left, &ty, "comparison");
+                convert_child(&mut node.right, &ty, "comparison");
+            } else {
+                panic!(
+                    "Type error: invalid operands to {} ({} and {})",
+                    node.val, left_ty, right_ty
+                );
+            }
+            node.ty = Some(Type::new(TypeKind::TyInt));
+        }
+        NodeKind::NdBitwiseNot => {
+            check_child(&mut node.left, context);
+            match &node.left {
+                Some(node_left) if node_type(node_left).is_integer() => {
+                    node.ty = Some(promoted_type(node_type(node_left)));
+                }
+                Some(node_left) => {
+                    panic!(
+                        "Type error: invalid operand to ~ ({})",
+                        node_type(node_left)
+                    );
+                }
+                _ => {
+                    panic!("Expected operand of ~");
+                }
+            }
+        }
+        NodeKind::NdUnaryOperator => {
+            check_child(&mut node.left, context);
+            match &node.left {
+                Some(node_left) if node_type(node_left).is_arithmetic() => {
+                    node.ty = Some(promoted_type(node_type(node_left)));
+                }
+                Some(node_left) => {
+                    panic!(
+                        "Type error: invalid operand to unary {} ({})",
+                        node.val,
+                        node_type(node_left)
+                    );
+                }
+                _ => {
+                    panic!("Expected operand of {}", node.val);
+                }
+            }
+        }
+        NodeKind::NdLogicalOperator => {
+            check_child(&mut node.left, context);
+            check_child(&mut node.right, context);
+            let (left_ty, right_ty) = operand_types(node);
+            let is_scalar = |ty: &Type| ty.is_arithmetic() || ty.is_pointer();
+            if !is_scalar(&left_ty) || !is_scalar(&right_ty) {
+                panic!(
+                    "Type error: invalid operands to {} ({} and {})",
+                    node.val, left_ty, right_ty
+                );
+            }
+            convert_condition(&mut node.left);
+            convert_condition(&mut node.right);
+            node.ty = Some(Type::new(TypeKind::TyInt));
+        }
+        NodeKind::NdAddress => {
+            check_child(&mut node.left, context);
+            match &node.left {
+                Some(node_left) if is_lvalue(node_left) => {
+                    node.ty = Some(Type::pointer_to(node_type(node_left).clone()));
+                }
+                _ => {
+                    panic!("Type error: cannot take the address of an rvalue");
+                }
+            }
+        }
+        NodeKind::NdDereference => {
+            check_child(&mut node.left, context);
+            match &node.left {
+                Some(node_left) => {
+                    let ty = &node_type(node_left).decay();
+                    if !ty.is_pointer() || pointee_type(ty).is_void() {
+                        panic!("Type error: invalid operand to unary * ({})", ty);
+                    }
+                    node.ty = Some(complete_type(context, pointee_type(ty)));
+                }
+                _ => {
+                    panic!("Expected operand of *");
+                }
+            }
+        }
+        NodeKind::NdMember => {
+            check_child(&mut node.left, context);
+            let ty = match &node.left {
+                Some(node_left) => complete_type(context, node_type(node_left)),
+                _ => {
+                    panic!("Expected operand of .");
+                }
+            };
+            if !ty.is_struct() {
+                panic!(
+                    "Type error: request for member {} in something not a structure ({})",
+                    node.val, ty
+                );
+            }
+            match ty.members.iter().find(|member| member.name == node.val) {
+                Some(member) => {
+                    // the members of a const struct are const as well
+                    let mut member_ty = complete_type(context, &member.ty);
+                    member_ty.is_const |= ty.is_const;
+                    node.ty = Some(member_ty);
+                    node.offset = member.offset;
+                }
+                _ => {
+                    panic!("Type error: {} has no member named {}", ty, node.val);
+                }
+            }
+        }
+        NodeKind::NdSizeof => {
+            // sizeof is replaced with its value, so the operand is never evaluated
+            check_child(&mut node.left, context);
+            let ty = match (&node.left, &node.ty) {
+                (Some(node_left), _) => node_type(node_left).clone(),
+                (_, Some(ty)) => resolve_type(context, ty),
+                _ => {
+                    panic!("Expected operand of sizeof");
+                }
+            };
+            if !is_complete(&ty) {
+                panic!("Type error: invalid application of sizeof to {}", ty);
+            }
+            // the value is a size_t, which is unsigned long
+            let mut size_ty = Type::new(TypeKind::TyLong);
+            size_ty.is_unsigned = true;
+            *node = Node::new(&ty.size.to_string(), NodeKind::NdNum);
+            node.ty = Some(size_ty);
+        }
+        NodeKind::NdCall => {
+            for arg in node.args.iter_mut() {
+                check(arg, context);
+            }
+            // calling a variable by its name calls the function it points to
+            if node.left.is_none() && find_variable(context, &node.val).is_some() {
+                node.left = Some(Box::new(Node::new(&node.val, NodeKind::NdVariable)));
+            }
+            let function_ty = match &mut node.left {
+                Some(callee) => {
+                    check(callee, context);
+                    let ty = node_type(callee).decay();
+                    match &ty.base {
+                        Some(base) if ty.is_pointer() && base.is_function() => {
+                            Some((**base).clone())
+                        }
+                        _ => {
+                            panic!("Type error: called object is not a function ({})", ty);
+                        }
+                    }
+                }
+                _ => context.functions.get(&node.val).cloned(),
+            };
+            // an undeclared function is assumed to return long and its arguments are passed as is
+            match function_ty {
+                Some(function_ty) => {
+                    let param_count = function_ty.param_tys.len();
+                    if param_count != node.args.len()
+                        && !(function_ty.is_variadic && param_count < node.args.len())
+                    {
+                        let at_least = if function_ty.is_variadic {
+                            "at least "
+                        } else {
+                            ""
+                        };
+                        panic!(
+                            "Type error: {} takes {}{} arguments but {} given",
+                            node.val,
+                            at_least,
+                            param_count,
+                            node.args.len()
+                        );
+                    }
+                    // the type may have been collected before its typedef names were defined
+                    let function_ty = resolve_type(context, &function_ty);
+                    let mut args = std::mem::take(&mut node.args);
+                    let variadic_args = args.split_off(param_count);
+                    node.args = args
+                        .into_iter()
+                        .zip(function_ty.param_tys.iter())
+                        .map(|(arg, ty)| convert(arg, ty, "argument"))
+                        .collect();
+                    // a float matched by `...` is promoted to double, and the code generator
+                    // passes it in an integer register as the callee reads it from memory
+                    if function_ty.is_variadic {
+                        node.fixed_arg_count = Some(param_count);
+                        for arg in variadic_args {
+                            check_argument(node_type(&arg));
+                            let arg = match node_type(&arg).kind {
+                                TypeKind::TyFloat => {
+                                    convert(arg, &Type::new(TypeKind::TyDouble), "argument")
+                                }
+                                _ => arg,
+                            };
+                            node.args.push(arg);
+                        }
+                    }
+                    if let Some(return_ty) = &function_ty.base {
+                        node.ty = Some((**return_ty).clone());
+                    }
+                }
+                _ => {
+                    for arg in node.args.iter() {
+                        check_argument(node_type(arg));
+                    }
+                    node.ty = Some(Type::new(TypeKind::TyLong));
+                }
+            }
+        }
+    }
+}
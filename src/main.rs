@@ -8,7 +8,8 @@ fn main() {
     let input: String = [&input, " "].join("");
 
     let tokens = compiler::lexing(&input);
-    let ast = compiler::parsing(&tokens);
+    let mut ast = compiler::parsing(&tokens);
+    compiler::type_checking(&mut ast);
     let mid_commands = compiler::generate_intermediate_code(&ast);
     let native_commands = compiler::generate_native_code(&mid_commands);
 
@@ -3,7 +3,7 @@
 assert() {
     in=$1
     ans=$2
-    cargo run -- $1 > out/asm.S
+    cargo run -- "$1" > out/asm.S
 
     riscv64-unknown-elf-gcc out/asm.S -o out/run
     qemu-riscv64 out/run
@@ -18,6 +18,36 @@ assert() {
     fi
 }
 
+assert_output() {
+    in=$1
+    ans=$2
+    cargo run -- "$1" > out/asm.S
+
+    riscv64-unknown-elf-gcc out/asm.S -o out/run
+    res=$(qemu-riscv64 out/run)
+
+    if [ "$res" == "$ans" ]; then
+        echo "OK"
+    else
+        echo -e "\033[0;31mNG\033[0;39m"
+        echo "Result: $res, Ans: $ans"
+    fi
+}
+
+assert_error() {
+    in=$1
+    ans=$2
+    cargo run -- "$1" > out/asm.S 2> out/err
+    res=$?
+
+    # an ill-formed program is rejected with a nonzero exit status and the diagnostic
+    if [ $res != 0 ] && grep -qF -- "$ans" out/err; then
+        echo "OK"
+    else
+        echo -e "\033[0;31mNG\033[0;39m"
+        echo "Result: $res, Ans: $ans"
+    fi
+}
 
 cd $(cd $(dirname ${BASH_SOURCE:-$0}); pwd)
 mkdir -p out
@@ -60,3 +90,345 @@ assert "abc=3*2; bcde=2; c=2+bcde*abc;" 14
 assert "abc=3*2; xy=6; abc==xy;" 1
 assert "abc=3*2; xy=10; abc==xy; return xy;" 10
 assert "abc=3*2; xy=10; return xy; abc==xy;" 10
+assert "a=3; if (a==3) b=1; else b=2; b;" 1
+assert "a=4; if (a==3) b=1; else b=2; b;" 2
+assert "a=0; if (1) a=5; a;" 5
+assert "a=0; if (0) a=5; a;" 0
+assert "if (1) if (0) return 1; else return 2; return 3;" 2
+assert "a=2; if (a<1) return 1; else if (a<3) return 2; else return 3;" 2
+assert "if (0) return 1; 7;" 7
+assert "i=0; while (i<10) i=i+1; i;" 10
+assert "i=0; while (0) i=i+1; i;" 0
+assert "s=0; for (i=0; i<=10; i=i+1) s=s+i; s;" 55
+assert "i=0; for (;;) if (i==5) break; else i=i+1; i;" 5
+assert "i=0; while (1) if (i==7) break; else i=i+1; i;" 7
+assert "s=0; for (i=0; i<10; i=i+1) if (i<5) continue; else s=s+i; s;" 35
+assert "s=0; i=0; while (i<10) if ((i=i+1)<=5) continue; else s=s+i; s;" 40
+assert "s=0; for (i=0; i<3; i=i+1) for (j=0; j<10; j=j+1) if (j==2) break; else s=s+1; s;" 6
+assert "{ 1; { 2; } 3; }" 3
+assert "int x=1; { int x=2; x=x+5; } x;" 1
+assert "int x=1; { int x=2; x=x+5; return x; }" 7
+assert "int x=1; { x=x+5; } x;" 6
+assert "int a=1, b=2; { int a=10; b=a+b; } a+b;" 13
+assert "int s=0; for (int i=0; i<5; i=i+1) s=s+i; int i=100; s+i;" 110
+assert "int s=0; for (int i=0; i<3; i=i+1) { int j=i*2; s=s+j; } s;" 6
+assert "{ int a=3; } { int b=4; b; }" 4
+assert "ret3() { return 3; } main() { return ret3(); }" 3
+assert "add(a, b) { return a+b; } main() { return add(3, 4); }" 7
+assert "sub(a, b) { return a-b; } main() { return sub(10, 4); }" 6
+assert "int fib(int n) { if (n<2) return n; return fib(n-1)+fib(n-2); } int main() { return fib(10); }" 55
+assert "add8(a,b,c,d,e,f,g,h) { return a+b+c+d+e+f+g+h; } main() { return add8(1,2,3,4,5,6,7,8); }" 36
+assert "f10(a,b,c,d,e,f,g,h,i,j) { return i*2+j; } main() { return f10(1,2,3,4,5,6,7,8,9,10); }" 28
+assert "f10(a,b,c,d,e,f,g,h,i,j) { return a+b+c+d+e+f+g+h+i+j; } main() { return f10(1,2,3,4,5,6,7,8,9,10); }" 55
+assert "f9(a,b,c,d,e,f,g,h,i) { return a-i; } main() { int x = 2; return f9(20,2,3,4,5,6,7,8,x+1)+x; }" 19
+assert "twice(x) { return x*2; } main() { return twice(twice(3)+1); }" 14
+assert "sum(n) { int s=0; for (int i=1; i<=n; i=i+1) s=s+i; return s; } main() { return sum(10); }" 55
+assert "f() { 5; } main() { return f(); }" 5
+assert "putchar(65); 3;" 3
+assert "x = 1 + putchar(65); x;" 66
+assert "extern int putchar(int c); int main() { return putchar(66); }" 66
+assert "int putchar(int); int main() { return putchar(66); }" 66
+assert "main() { return 4294967296 / 65536 / 65536 + 2147483648 / 1073741824; }" 3
+assert_output "putchar(72); putchar(105); putchar(10);" "Hi"
+assert_output "extern putchar(c); print(n) { if (n<0) { putchar(45); n=0-n; } if (n>=10) print(n/10); putchar(48+n-n/10*10); } main() { print(4-18); putchar(10); print(1099511627776); }" "-14
+1099511627776"
+assert_output "f(a,b,c,d,e,f,g,h,i) { return putchar(i); } main() { 1 + f(1,2,3,4,5,6,7,8,75); }" "K"
+assert_output "main() { p = malloc(16); return putchar(p - p + 79); }" "O"
+assert_output "void *malloc(long n); int main() { int *p = malloc(16); p[0] = 79; p[3] = 75; putchar(p[0]); return putchar(p[3]); }" "OK"
+assert "void f(int *p) { *p = 3; return; } int main(void) { int x = 1; f(&x); return x; }" 3
+assert "int main() { int x = 2; void *p = &x; int *q = p; (void)q; return *(int *)p + *q; }" 4
+assert_error "void f() { return 1; } int main() { f(); return 0; }" "return with a value in a function returning void"
+assert_error "void f() {} int main() { return f(); }" "cannot convert void to int"
+assert_error "int main() { int x = 1; void *p = &x; return *p; }" "invalid operand to unary * (void *)"
+assert "char c=300; c;" 44
+assert "char c=127; c=c+1; c+200;" 72
+assert "int x=2147483647; x=x+1; x<0;" 1
+assert "int x=65536; x*x==0;" 1
+assert "long x=65536; x*x/4294967296;" 1
+assert "long x=2147483647; int y=1; x+y==2147483648;" 1
+assert "char a=1; int b=2; long c=3; char d=4; a+b+c+d;" 10
+assert "int *p; int **q; int x=3; x;" 3
+assert "char f(char c) { return c+1; } int main() { return f(255); }" 0
+assert "int g(long a, char b, int c) { return a-b-c; } int main() { return g(20, 3, 4); }" 13
+assert_error "int main() { int a = 1; return *a; }" "invalid operand to unary * (int)"
+assert_error "int main() { int x = 1; int *p = x; return 0; }" "cannot convert int to int *"
+assert "int x=3; int *p=&x; *p;" 3
+assert "int x=3; int *p=&x; *p=5; x;" 5
+assert "int x=3; int y=5; int *p=&x; *(p-1);" 5
+assert "int x=3; int y=5; int *p=&y; *(1+p);" 3
+assert "int x; int *p=&x; int *q=p+3; q-p;" 3
+assert "long x; long *p=&x; (p+2)-(p-1);" 3
+assert "char c=1; char *p=&c; char **pp=&p; **pp=7; c;" 7
+assert "int x=2; int *p=&x; (p==&x)+(p!=0)+(p+1>p)+(p<=p-1);" 3
+assert "int x=3; int y=-*&x; -y;" 3
+assert "void_swap(int *a, int *b) { int t=*a; *a=*b; *b=t; } int main() { int x=3; int y=10; void_swap(&x, &y); return x*2-y; }" 17
+assert "int *next(int *p) { return p+1; } int main() { int x=1; int y=2; *next(&y)=7; return x; }" 7
+assert "int a[3]; a[0]=1; a[1]=2; a[2]=3; a[0]+a[1]*a[2];" 7
+assert "int a[3]; *a=4; *(a+1)=5; a[0]+1[a];" 9
+assert "int a[3]; int *p=a; p[2]=7; *(a+2);" 7
+assert "char s[3]; s[0]=1; s[1]=2; int x=5; *(s+1)+x;" 7
+assert "char c=1; int a[3]; long l=2; char d=3; a[2]=4; c+l+d+a[2];" 10
+assert "int a[2][3]; int i; int j; for (i=0;i<2;i=i+1) for (j=0;j<3;j=j+1) a[i][j]=i*3+j; a[1][2]+a[0][1]*10;" 15
+assert "int a[2][3]; int *p=a[1]; a[1][1]=9; p[1];" 9
+assert "int a[2][3]; &a[1][0] - &a[0][0];" 3
+assert "long a[2][2][2]; a[1][1][1]=6; a[1][0][1]=1; a[1][1][1]-*(*(*(a+1))+1);" 5
+assert "{ long a[100]; a[99]=3; } long x=5; long b[4]; b[3]=x; b[3];" 5
+assert "int sum(int a[], int n) { int s=0; for (int i=0;i<n;i=i+1) s=s+a[i]; return s; } int main() { int a[4]; for (int i=0;i<4;i=i+1) a[i]=i+1; return sum(a, 4); }" 10
+assert "int x; int y=3; int main() { x=4; return x+y; }" 7
+assert "int x; int main() { return x; }" 0
+assert "long c=0-5; char d=300; int a[4]; int inc() { c=c+1; return c; } int main() { inc(); inc(); a[2]=d; return c+a[2]+10; }" 51
+assert "int g=7; int main() { int g=1; return g; }" 1
+assert "int g=7; int main() { { int g=1; } return g; }" 7
+assert "int g; int *p() { return &g; } int main() { *p()=9; return g; }" 9
+assert "int g; int *p = &g; struct { int a; int b[3]; } s; int *q = &s.b[1]; long a[4]; long *r = a + 3; int main() { *p = 9; *q = 4; *r = 2; return g + s.b[1] + a[3]; }" 15
+assert "int m[2][3]; int *t = m[1] + 1; int main() { *t = 6; return m[1][1] + sizeof(m); }" 30
+assert "long big=4294967296*2+3; int main() { return big/4294967296+big-8589934592; }" 5
+assert "int n=2*3+1; char s[4]; set(i, c) { s[i]=c; } main() { set(0, n); set(3, 2); return s[0]+s[3]+s[1]; }" 9
+assert "'a';" 97
+assert "'\\n'+'\\0'+'\\\\';" 102
+assert "char c='\\x41'; c-'\\101';" 0
+assert "'\\xff'+2;" 1
+assert "char *s=\"abc\"; s[1];" 98
+assert "char *s=\"abc\"; s[3];" 0
+assert "\"\\x41\\101\\t\\n\\\\\\\"\\0z\"[7]+'\\''+'\\0';" 161
+assert "int main() { char *p=\"ab\"; char *q=\"ab\"; return p==q; }" 1
+assert "char *msg = \"hi\"; char *msgs[] = {\"ab\", \"cd\" + 1}; int main() { return msg[1] - msgs[0][1] + msgs[1][0]; }" 107
+assert "int len(char *s) { int n=0; while (s[n]!=0) n=n+1; return n; } int main() { return len(\"hello, world\"); }" 12
+assert_output "int main() { printf(\"hello %d\\n\", 42); return 0; }" "hello 42"
+assert_output "int main() { puts(\"a  b;c%\"); return 0; }" "a  b;c%"
+assert_output "int main() { printf(\"%s=%c\\n\", \"x\", 'y'); return 0; }" "x=y"
+assert "struct P { int x; int y; } p; p.x=3; p.y=4; p.x*p.y;" 12
+assert "struct P { char c; long l; int i; } p; sizeof p;" 24
+assert "struct P { char c; int i; char d; } p; &p.d - &p.c;" 8
+assert "struct { char a; char b; } s; sizeof(s);" 2
+assert "union U { char c; long l; int i[3]; } u; u.l=258; sizeof(u)*100+u.c;" 66
+assert "struct { int a; struct { char b; int c; } in; } s; s.in.c=4; &s.in.c - &s.a + sizeof(s);" 14
+assert "struct P { int x; char y[3]; } a; struct P b; a.x=5; a.y[2]=7; b=a; a.x=0; b.x+b.y[2];" 12
+assert "struct P { int x; } a[3]; a[2].x=6; (a+1)->x=2; a[1].x+a[2].x+sizeof a;" 20
+assert "struct node { int v; struct node *next; }; int main() { struct node a; struct node b; a.v=1; b.v=2; a.next=&b; b.next=0; struct node *p=&a; int s=0; while (p!=0) { s=s*10+p->v; p=p->next; } return s; }" 12
+assert "struct Q *q; struct Q { int a; int b; }; struct Q v; q=&v; v.b=9; q->b;" 9
+assert "struct P { int x; }; int get(struct P *p) { return p->x; } int main() { struct P p; p.x=42; return get(&p); }" 42
+assert "struct P { int x; } g; int main() { g.x=3; struct P l; l=g; return l.x; }" 3
+assert "struct P { int x; } p; p.x=1; { struct P { long y; } q; q.y=8; p.x=p.x+sizeof q; } p.x;" 9
+assert "struct P { int x; int y; }; int main() { struct P a; struct P b; a.y=1; b.y=2; int c=0; return (c ? a : b).y*10 + (a = b).y; }" 22
+assert_error "struct P { int x; }; struct P mk(int x) { struct P p; p.x=x; return p; } int main() { return mk(10).x; }" "cannot return struct P by value"
+assert_error "struct P { int x; }; int f(struct P p) { return p.x; } int main() { struct P p; p.x=1; return f(p); }" "cannot pass struct P by value"
+assert_error "struct P { int x; }; int main() { struct P p; p.x=1; return g(p); }" "cannot pass struct P by value"
+assert "int x; sizeof x + sizeof(&x) + sizeof 'a';" 16
+assert "1&&2;" 1
+assert "0&&2;" 0
+assert "0||0;" 0
+assert "0||3;" 1
+assert "!0+!5+!!7;" 2
+assert "1<!0;" 0
+assert "int x=0; 0 && (x=5); 1 || (x=7); x;" 0
+assert "int x=0; 1 && (x=5); 0 || (x=x+7); x;" 12
+assert "int *p=0; !p + (p || 0);" 1
+assert "int a=1; int b=0; a && b || a && !b;" 1
+assert "int f(int x) { return x; } int main() { int a=1; return (a && f(2)) + f(3) + (a || f(4)); }" 5
+assert_output "int main() { int x=1; x + (x && x) + (x || 0) + printf(\"%d\\n\", x && 2); return 0; }" "1"
+assert "17%5;" 2
+assert "0-17%5+10;" 8
+assert "6&3;" 2
+assert "6|3;" 7
+assert "6^3;" 5
+assert "~5+10;" 4
+assert "1<<4;" 16
+assert "0-16>>2;" 252
+assert "1<<2+1;" 8
+assert "5&3==3;" 1
+assert "1|2^3&4;" 3
+assert "10 > 1 << 3;" 1
+assert "int x=1; x<<31 < 0;" 1
+assert "long x=1; (x<<40)>>38;" 4
+assert "char c=1; sizeof(c<<1)+sizeof(~c);" 8
+assert "int g=~(1<<4)&255|1; int main() { return g%100; }" 39
+assert "a=b=3; a+b;" 6
+assert "int a=5; a+=3; a-=1; a*=4; a/=2; a%=5; a;" 4
+assert "int a=1; a<<=4; a>>=1; a|=3; a&=10; a^=15; a;" 5
+assert "int x[3]; x[0]=1; x[1]=2; x[2]=3; int *p=x; p+=2; *p;" 3
+assert "int x[3]; x[0]=1; x[1]=2; x[2]=3; int i=0; x[i++]+=10; x[0]+i*100;" 111
+assert "int i=5; int j=i++; i*10+j;" 65
+assert "int i=5; int j=++i; i*10+j;" 66
+assert "int i=5; int j=i--; int k=--i; j*10+k;" 53
+assert "char c=127; int j=c++; j==127 && c==-128;" 1
+assert "int x[3]; x[0]=1; x[1]=2; x[2]=3; int *p=x; p++; int y=*p++; y*10+*p;" 23
+assert "int x=10; while (x-->5); x;" 4
+assert "struct { int a; } s; s.a=3; s.a+=4; s.a++; s.a;" 8
+assert "1 ? 5 : 7;" 5
+assert "0 ? 5 : 7;" 7
+assert "int a=0; int b=0; 1 ? (a=3) : (b=4); a*10+b;" 30
+assert "int a=2; a==1 ? 10 : a==2 ? 20 : 30;" 20
+assert "int x[2]; x[0]=4; x[1]=9; int *p = 0 ? 0 : x+1; *p;" 9
+assert "long x=2; sizeof(0 ? 1 : x);" 8
+assert "int f(int x) { return x > 0 ? x + f(x - 1) : 0; } int main() { return f(10); }" 55
+assert "int g = 1 ? 42 : 7; int main() { return g; }" 42
+assert "int a=1, b=2; (a=5, b=a+1, a*b);" 30
+assert "int i; int j; int s=0; for (i=0, j=10; i<j; i++, j--) s=s+1; s;" 5
+assert "int f(int x) { switch (x) { case 0: return 10; case 1: return 11; case 2: return 12; case 3: return 13; case 5: return 15; default: return 99; } } int main() { return f(0)+f(3)+f(4)+f(5)+f(100)-f(7); }" 137
+assert "int x=2; int r=0; switch (x) { case 1: r=r+1; case 2: r=r+2; case 3: r=r+3; break; case 4: r=r+4; } r;" 5
+assert "int x=1000; int r=0; switch (x) { case 1: r=1; break; case 1000: r=7; break; case 99999: r=9; } r;" 7
+assert "int x=5; int r=3; switch (x) { case 1: r=1; } r;" 3
+assert "int r=0; int i; for (i=0; i<5; i++) { switch (i) { case 1: continue; case 3: break; default: r=r+10; } r=r+1; } r;" 34
+assert "char c=-1; int r=0; switch (c) { case 255: r=1; break; case -1: r=2; break; } r;" 2
+assert "int x=1; int y=2; int r=0; switch (x) { case 1: switch (y) { case 2: r=5; break; default: r=6; } r=r+10; break; case 2: r=100; } r;" 15
+assert "int x=7; int r=0; switch (x) { default: r=9; break; case 3: r=3; } r;" 9
+assert "int i=0; do i=i+3; while (i<10); i;" 12
+assert "int i=0; do { i++; if (i==2) continue; if (i==5) break; } while (1); i;" 5
+assert "int i=50; do i++; while (0); i;" 51
+assert "int i=0; int s=0; loop: s=s+i; i++; if (i<5) goto loop; s;" 10
+assert "int i=0; goto end; i=7; end: i;" 0
+assert "int f() { goto x; x: return 1; } int g() { goto x; x: return 2; } int main() { return f()*10+g(); }" 12
+assert_error "int main() { goto nowhere; return 0; }" "Label nowhere used but not defined in main"
+assert "int x=1; switch (x) { case 1: goto done; case 2: x=5; } done: x;" 1
+assert "enum { A, B, C }; C;" 2
+assert "enum E { A=5, B, C=10, D }; B*10+D;" 71
+assert "enum E { A=1<<2, B=A+3, }; enum E x = B; x;" 7
+assert "enum { N=-3 }; N+5;" 2
+assert "enum { K=3 }; int g[K]; int main() { return sizeof(g); }" 12
+assert "enum { A=1, B=2 }; int x=2; switch (x) { case A: 10; case B: return 20; } 0;" 20
+assert "enum { A=1, B=2 }; int A2 = A; int main() { int A = 7; return A + A2; }" 8
+assert "enum { A = 1 == 1, B = 2 < 1 || 3 >= 3, C = !0 + (0 && 1/0) }; int main() { return A * 100 + B * 10 + C; }" 111
+assert "int main() { switch (1) { case 1+1 == 2: return 5; } return 0; }" 5
+assert "int g = 1 < 2; int h = -1 < 0u; int i = 1.5 > 1.0; int main() { return g * 100 + h * 10 + i; }" 101
+assert "typedef int T; T x = 7; x;" 7
+assert "typedef int *P, A[3]; A a; P p = a; a[1]=4; sizeof(a) + p[1];" 16
+assert "typedef struct { int x; int y; } Point; Point p; p.x=3; p.y=4; p.x*p.y;" 12
+assert "typedef struct node node; struct node { int v; node *next; }; node a; node b; a.v=1; b.v=2; a.next=&b; a.next->v;" 2
+assert "typedef int T; int main() { T x = 3; { typedef char T; T y; x = x + sizeof(y); } T z; return x + sizeof(z); }" 8
+assert "typedef enum { RED, GREEN=4, BLUE } Color; Color c = BLUE; c;" 5
+assert "typedef long L; L f(L a) { return a * 2; } int main() { return f(21); }" 42
+assert "typedef int T; typedef T U; U u = 9; u;" 9
+assert "struct S { int T; }; typedef int T; struct S s; s.T = 3; T t = s.T; t;" 3
+assert "typedef int T; int main() { int T = 3; return T; }" 3
+assert "typedef int T; int f(int T) { return T; } int main() { return f(4); }" 4
+assert "typedef int T; int main() { T x = 2; { int T = 5; x = x + T; } T y = 1; return x + y; }" 8
+assert "typedef int T; int main() { int s = 0; for (int T = 0; T < 3; T++) s += T; T x = s; return x; }" 3
+assert "typedef int T; int main() { enum { T = 6 }; return T; }" 6
+assert "typedef long T; int main() { T T = 1; return sizeof(T); }" 8
+assert "typedef int T; T main() { return 1; }" 1
+assert_error "typedef int T; int T; int main() { return 0; }" "Redefinition of T"
+assert "sizeof(int);" 4
+assert "(sizeof(int) - 5 < 0) + (-1 < sizeof(int)) * 2 + (sizeof(char) - 2 > 0) * 4;" 4
+assert "sizeof(long) + sizeof(char) + sizeof(int*) + sizeof(int[3]);" 29
+assert "sizeof(struct { int a; char b; });" 8
+assert "typedef int T; sizeof(T) * 2;" 8
+assert "int x; sizeof x + sizeof(x);" 8
+assert "int x = 300; (char)x;" 44
+assert "int x = 200; (char)x == -56;" 1
+assert "long x = 4294967297; (int)x;" 1
+assert "int x = -1; long y = (long)x; y == -1;" 1
+assert "int a[2]; a[0]=3; a[1]=5; long p = (long)a; *(int*)(p + 4);" 5
+assert "long g = (char)300; int main() { return g; }" 44
+assert "int x = 3; -(long)x + 10;" 7
+assert "unsigned int x = -1; x > 0;" 1
+assert "-1 < 0u;" 0
+assert "-1 < 0;" 1
+assert "unsigned char c = 255; c;" 255
+assert "char c = 255; c == -1;" 1
+assert "(unsigned)-1 / 2 == 2147483647;" 1
+assert "unsigned x = 4294967295u; (long)x == 4294967295;" 1
+assert "-1 >> 1 == -1;" 1
+assert "(unsigned)-1 >> 31;" 1
+assert "unsigned long x = -1; x / 2 == 9223372036854775807;" 1
+assert "unsigned long x = -1; x % 10;" 5
+assert "int x = -7; unsigned y = 2; x % y;" 1
+assert "sizeof(unsigned long) + sizeof(unsigned char) + sizeof(signed);" 13
+assert "unsigned char c = 255; c + 1 == 256;" 1
+assert "unsigned char a[2]; a[0] = 200; a[1] = 100; a[0] + a[1];" 44
+assert "unsigned g = -1; int main() { return g > 1; }" 1
+assert "unsigned g = (unsigned)-1 / 2; int main() { return g == 2147483647; }" 1
+assert "unsigned x = 0; ~x == 4294967295;" 1
+assert "switch ((unsigned char)-1) { case 255: 7; break; default: 3; }" 7
+assert "unsigned f() { return 4294967295u; } int main() { long (*g)() = (long (*)())f; return g() == -1; }" 1
+assert "long id(long x) { return x; } int main() { long (*g)(unsigned) = (long (*)(unsigned))id; return g(4294967295u) == -1; }" 1
+assert "long m() { return -1; } int main() { unsigned (*g)() = (unsigned (*)())m; return g() / 2 == 2147483647u; }" 1
+assert "double x = 1.5; x * 2 == 3.0;" 1
+assert "double x = 1.5; int y = x * 3; y;" 4
+assert "float f = 2.5f; (int)(f * 4);" 10
+assert "(int)1e2 + (int).5e1;" 105
+assert "double d = -7.9; (int)d;" 249
+assert "int main() { double x = 0.1 + 0.2; return x == 0.3; }" 0
+assert "float f = 1; double d = f / 3; d > 0.33 && d < 0.34;" 1
+assert "double d = 1.0/3; float f = d; (double)f == d;" 0
+assert "double a = 1.25; a += 2; a++; (int)(a * 4);" 17
+assert "double d = 0.1; double e = d++; e == 0.1;" 1
+assert "float f = 0.1f; float g = f--; g == 0.1f;" 1
+assert "double d = 0.1; double *p = &d; double e = (*p)--; e == 0.1;" 1
+assert "double x = 0; if (x) 1; else 2;" 2
+assert "double x = 0.5; !x + (x && 1) * 2 + (x ? 4 : 8);" 6
+assert "unsigned long u = -1; double d = u; d > 0;" 1
+assert "double d = 3000000000.0; unsigned x = d; x == 3000000000u;" 1
+assert "char c = 65.7; c;" 65
+assert "sizeof(double) + sizeof(float) + sizeof(1.0f) + sizeof(1.0);" 24
+assert "double g = 2.5; float h = 1.5; int i = 7.9; int main() { return g * h * 4 + i; }" 22
+assert "double f(double a, int b, float c) { return a * b + c; } int main() { return f(1.5, 4, 0.5f); }" 6
+assert "float g(float x) { return x * x; } int main() { return g(1.5f) * 4; }" 9
+assert "long f(double a,double b,double c,double d,double e,double f,double g,double h,double i,long j){return i*10+j;} int main(){return f(1,2,3,4,5,6,7,8,9,3);}" 93
+assert "long f(long a,long b,long c,long d,long e,long f,long g,long h,double x,long i,double y){return x*10+i+y;} int main(){return f(1,2,3,4,5,6,7,8,4.0,2,0.5);}" 42
+assert "double sqrt(double); int main() { return sqrt(16.0) + sqrt(9); }" 7
+assert "struct S { char c; double d; }; struct S s; s.d = 2.5; s.c = 1; (int)(sizeof(s) + s.d * 2);" 21
+assert "int add(int a, int b) { return a + b; } int main() { int (*f)(int, int) = add; return f(3, 4); }" 7
+assert "int add(int a, int b) { return a + b; } int main() { int (*f)(int, int) = &add; return (*f)(3, 4); }" 7
+assert "int add(int a, int b) { return a + b; } int sub(int a, int b) { return a - b; } int (*ops[2])(int, int); int main() { ops[0] = add; ops[1] = sub; return ops[0](10, 2) * 10 + ops[1](10, 2); }" 128
+assert "int twice(int x) { return x * 2; } struct S { int (*f)(int); int y; }; int main() { struct S s; s.f = twice; s.y = 5; return s.f(s.y); }" 10
+assert "typedef int (*unop)(int); int inc(int x) { return x + 1; } int apply(unop f, int x) { return f(f(x)); } int main() { return apply(inc, 40); }" 42
+assert "int inc(int x) { return x + 1; } int apply(int f(int), int x) { return f(x); } int main() { return apply(inc, 1); }" 2
+assert "double half(double x) { return x / 2; } int main() { double (*f)(double) = half; return f(9) * 2; }" 9
+assert "int f(int x) { return x; } int main() { int (*p)(int) = f; return p == f && p != 0; }" 1
+assert "int f(int x) { return x * 3; } int main() { long p = (long)f; return sizeof(int (*)(int)) + ((int (*)(int))p)(5); }" 23
+assert "int putchar(int); int main() { int (*f)(int) = putchar; return f(65); }" 65
+assert "int a() { return 1; } int b() { return 2; } int (*u)() = b; int (*v)() = a; int main() { return u() * 10 + v(); }" 21
+assert "int a() { return 1; } int b() { return 2; } int (*t[2])() = {a, b}; int (*u)() = b; int main() { return t[0]() * 10 + t[1]() + u(); }" 14
+assert "int main() { int g(int); return g(4); } int g(int x) { return x + 1; }" 5
+assert "int a1(int a,int b,int c,int d,int e,int f,int g,int h,int i,int j){return i*10+j;} int main(){ int (*p)(int,int,int,int,int,int,int,int,int,int) = a1; int x = 1; return x + p(1,2,3,4,5,6,7,8,9,3);}" 94
+assert "int sum(int n, ...) { va_list ap; va_start(ap, n); int s = 0; for (int i = 0; i < n; i++) s += va_arg(ap, int); va_end(ap); return s; } int main() { return sum(4, 1, 2, 3, 4); }" 10
+assert "int sum(int n, ...) { va_list ap; va_start(ap, n); int s = 0; for (int i = 0; i < n; i++) s += va_arg(ap, int); va_end(ap); return s; } int main() { return sum(10, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10); }" 55
+assert "double avg(int n, ...) { va_list ap; va_start(ap, n); double s = 0; for (int i = 0; i < n; i++) s += va_arg(ap, double); va_end(ap); return s / n; } int main() { return avg(3, 1.5, 2.5f, 8.0); }" 4
+assert "long f(long a, long b, long c, long d, long e, long f, long g, long h, long i, ...) { va_list ap; va_start(ap, i); return va_arg(ap, long) + i; } int main() { return f(1, 2, 3, 4, 5, 6, 7, 8, 9, 30); }" 39
+assert "int vsum(int n, va_list ap) { int s = 0; while (n--) s += va_arg(ap, int); return s; } int sum(int n, ...) { va_list ap; va_start(ap, n); return vsum(n, ap); } int main() { return sum(3, 5, 6, 7); }" 18
+assert "int f(int n, ...); int main() { int (*p)(int, ...) = f; return p(2, 20, 22); } int f(int n, ...) { va_list ap; va_start(ap, n); return va_arg(ap, int) + va_arg(ap, int); }" 42
+assert_output "int printf(char *fmt, ...); int main() { float f = 0.25f; printf(\"%d %f %.2f\n\", 3, 1.5, f); return 0; }" "3 1.500000 0.25"
+assert_output "int printf(char *fmt, ...); double g = -0.0; int main() { double z = 0.0; float f = 0.0f; printf(\"%g %g %g %g\n\", -z, -f, +-z, g); return 0; }" "-0 -0 -0 -0"
+assert "unsigned x = 5; int y = 3; (-x > 0) * 10 + -+-y;" 13
+assert "int main() { int a[] = {1, 2, 3}; return a[0] + a[1] * a[2] + sizeof(a); }" 19
+assert "int main() { int a[5] = {1, 2}; return a[0] + a[1] + a[2] + a[3] + a[4]; }" 3
+assert "int main() { int a[2][3] = {{1, 2}, {3}}; return a[0][1] * 10 + a[1][0] + a[1][2]; }" 23
+assert "int main() { int a[2][2] = {1, 2, 3, 4}; return a[1][1] * 10 + a[0][1]; }" 42
+assert "int main() { int a[10] = {[3] = 7, 8, [1] = 2}; return a[3] * 10 + a[4] + a[1] + a[0] + a[9]; }" 80
+assert "struct P { int x; char c; long y; }; int main() { struct P p = {.y = 5, .x = 3}; return p.x + p.y + p.c; }" 8
+assert "struct P { int x; int y; }; int main() { struct P ps[] = {{1, 2}, 3, 4, [3].y = 9}; return sizeof(ps) / sizeof(ps[0]) + ps[1].y * 10 + ps[3].y + ps[2].x; }" 53
+assert "struct P { int x; int y; }; int main() { struct P p = {1, 2}; struct P a[2] = {p, {3, 4}}; return a[0].y + a[1].x; }" 5
+assert "union U { int i; char c[4]; }; int main() { union U u = {258}; union U v = {.c = {1, 1}}; return u.c[1] + v.i; }" 2
+assert "int main() { char s[] = \"abc\"; return sizeof(s) + s[2] - 99 + s[3]; }" 4
+assert "int main() { char s[8] = \"hi\"; return s[1] + s[7] + s[2]; }" 105
+assert "char names[][4] = {\"ab\", \"cde\"}; int main() { return names[1][2] + sizeof(names); }" 109
+assert "int g[] = {1, 2, 3}; long h = 5; struct { char c; long l; int i; } s = {1, 2, 3}; int main() { return g[2] + h + sizeof(g) + s.c + s.l + s.i; }" 26
+assert "int g[3]; struct P { int x; int *p; char *s; } ps[2] = {[1] = {.s = \"xyz\", .p = &g[2]}, [0].x = 1}; int main() { *ps[1].p = 5; return g[2] + ps[1].s[2] + ps[0].x; }" 128
+assert "double d[3] = {1.5, 2}; float f = 2.5f; int main() { return d[0] + d[1] + d[2] + f; }" 6
+assert "int main() { int x = 4; int a[3] = {x, x * 2}; return a[1] + a[0]; }" 12
+assert "int count() { static int n; n++; return n; } int main() { count(); count(); return count(); }" 3
+assert "int count() { static int n = 10; return n++; } int main() { count(); count(); return count(); }" 12
+assert "int f(int x) { if (x) { static int n = 1; return n++; } static int n = 100; return n++; } int main() { f(1); f(0); return f(1) + f(0); }" 103
+assert "static int x = 3; static int twice(int v) { return v * 2; } int main() { return twice(x); }" 6
+assert "static int twice(int v); int main() { return twice(4); } int twice(int v) { return v * 2; }" 8
+assert "extern int x; int main() { return x; } int x = 5;" 5
+assert "int main() { extern int y; return y + 1; } int y = 7;" 8
+assert "int x = 7; int main() { int x = 1; { extern int x; return x; } }" 7
+assert "int x = 7; int g() { return x; } int main() { int x = 1; { extern int x; x = x + 1; } return g() * 10 + x; }" 81
+assert "int x; int x; int main() { x = 3; return x; }" 3
+assert "int x; int x = 4; extern int x; int x; int main() { return x; }" 4
+assert "int x; int f() { return x; } int x = 3; int main() { return f(); }" 3
+assert "static int x; int f() { return x; } static int x = 6; int main() { return f(); }" 6
+assert_error "int x = 1; int x = 2; int main() { return x; }" "Redefinition of x"
+assert_error "int x; long x; int main() { return 0; }" "conflicting types for x"
+assert "int main() { const int x = 3; const int a[2] = {1, 2}; return x + a[1]; }" 5
+assert "int main() { int a = 1; int *const p = &a; *p = 5; const int *q = p; return *q; }" 5
+assert "typedef const int CI; const int k = 2; int main() { CI x = 4; return x + k; }" 6
+assert "const int k = 5; const int a[] = {1, 2}; int g; int *const p = &g; int main() { *p = 3; return k + a[1] + g; }" 10
+assert "const int k = 5; int main() { *(int *)&k = 1; return k; }" 139
+assert "int main() { volatile int x = 4; int volatile *p = &x; *p = 6; return x + sizeof(const char); }" 7
+assert_error "int main() { const int c = 1; c = 2; return c; }" "cannot assign to a read-only object"
+assert_error "const int c = 1; int *p = &c; int main() { return *p; }" "conversion from const int * to int * in initialization discards const"
+assert_error "int f(int *p) { return *p; } int main() { const int c = 1; return f(&c); }" "in argument discards const"
+assert "int main() { const int a[2] = {1, 2}; const int *p = a; int x = 3; int *q = &x; return (p == q) + (q != p) + *(1 ? q : p); }" 4